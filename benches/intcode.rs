@@ -0,0 +1,90 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use advent_of_code_2019::day2::generate_input;
+use advent_of_code_2019::intcode::jit::JitMachine;
+use advent_of_code_2019::intcode::Machine;
+
+/// The day 2 part 2 search, minus the early exit so every iteration does the same work.
+///
+/// This is the baseline, the loop as it was before decoded instructions were cached: a fresh copy
+/// of the program for every noun and verb, run to completion without the cache. It's what
+/// `execute` does, but `execute` can't turn the cache off.
+fn fresh_noun_verb_search(program: &[i64]) -> i64 {
+    let mut found = 0;
+
+    for noun in 0..100 {
+        for verb in 0..100 {
+            let mut memory = program.to_vec();
+            memory[1] = noun;
+            memory[2] = verb;
+            let mut machine = Machine::new(memory).with_decode_cache(false);
+            if machine.run_to_completion().is_ok() && machine.memory().get(0) == 19_690_720 {
+                found = noun * 100 + verb;
+            }
+        }
+    }
+    found
+}
+
+/// The same search on one `Machine`, restored from a snapshot for every noun and verb.
+fn noun_verb_search(program: &[i64], cache_decoded: bool) -> i64 {
+    let mut machine = Machine::new(program.to_vec()).with_decode_cache(cache_decoded);
+    let start = machine.snapshot();
+    let mut found = 0;
+
+    for noun in 0..100 {
+        for verb in 0..100 {
+            machine.restore(&start);
+            machine.set_memory(1, noun);
+            machine.set_memory(2, verb);
+            if machine.run_to_completion().is_ok() && machine.memory().get(0) == 19_690_720 {
+                found = noun * 100 + verb;
+            }
+        }
+    }
+    found
+}
+
+fn compiled_noun_verb_search(program: &[i64]) -> i64 {
+    let mut machine = JitMachine::new(program.to_vec());
+    let start = machine.snapshot();
+    let mut found = 0;
+
+    for noun in 0..100 {
+        for verb in 0..100 {
+            machine.restore(&start);
+            machine.set_memory(1, noun);
+            machine.set_memory(2, verb);
+            if machine.run_to_completion().is_ok()
+                && machine.machine().memory().get(0) == 19_690_720
+            {
+                found = noun * 100 + verb;
+            }
+        }
+    }
+    found
+}
+
+fn day2_search(c: &mut Criterion) {
+    let program = generate_input(include_str!("../input/2019/day2.txt").trim());
+
+    let mut group = c.benchmark_group("day2 noun/verb search");
+    group.bench_function("fresh machine, cache disabled", |b| {
+        b.iter(|| fresh_noun_verb_search(black_box(&program)))
+    });
+    // The snapshotting machine with its decode cache turned off, to separate what the cache
+    // saves from what restoring a snapshot instead of copying the program saves.
+    group.bench_function("machine, cache disabled", |b| {
+        b.iter(|| noun_verb_search(black_box(&program), false))
+    });
+    group.bench_function("machine, decode cache", |b| {
+        b.iter(|| noun_verb_search(black_box(&program), true))
+    });
+    group.bench_function("machine, compiled", |b| {
+        b.iter(|| compiled_noun_verb_search(black_box(&program)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, day2_search);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+use std::io::{self, BufRead, Write};
+use std::{env, process};
+
+use advent_of_code_2019::intcode::ascii::AsciiMachine;
+use advent_of_code_2019::intcode::{read_program, Machine, Status};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-ascii <program>");
+            process::exit(2);
+        }
+    };
+    let program = match read_program(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut machine = AsciiMachine::new(Machine::new(program));
+    let stdin = io::stdin();
+    loop {
+        let status = machine.run();
+
+        print!("{}", machine.take_text());
+        for value in machine.take_values() {
+            println!("[{}]", value);
+        }
+        io::stdout().flush().unwrap();
+
+        match status {
+            Ok(Status::AwaitingInput) => {}
+            Ok(_) => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        machine.push_line(line.trim_end_matches(&['\r', '\n'][..]));
+    }
+}
@@ -0,0 +1,80 @@
+use std::{env, process};
+
+use advent_of_code_2019::intcode::analysis::analyze_from;
+use advent_of_code_2019::intcode::{read_program, Machine, StepResult};
+
+const USAGE: &str = "\
+Usage: intcode-cfg <program> [steps [input...]] > program.dot
+
+Prints the program's control-flow graph in Graphviz DOT format, and anything suspicious about
+it to stderr. Given a number of steps, the program is run for that many instructions first,
+with the given input, and analysed from wherever it got to.";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let numbers = args
+        .iter()
+        .skip(1)
+        .map(|arg| arg.parse::<i64>())
+        .collect::<Result<Vec<_>, _>>();
+    let (path, steps, input) = match (args.first(), numbers) {
+        (Some(path), Ok(numbers)) => match numbers.split_first() {
+            Some((steps, input)) if *steps >= 0 => (path, *steps, input.to_vec()),
+            Some(_) => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+            None => (path, 0, Vec::new()),
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let program = match read_program(path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut machine = Machine::new(program);
+    machine.extend_input(input);
+    for _ in 0..steps {
+        match machine.step() {
+            Ok(None) | Ok(Some(StepResult::Output(_))) => {}
+            Ok(Some(_)) => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    let memory = match machine.memory().to_vec() {
+        Ok(memory) => memory,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    let analysis = analyze_from(&memory, machine.ip());
+    print!("{}", analysis.to_dot());
+
+    for address in analysis.indirect_jumps() {
+        eprintln!("{}: indirect jump", address);
+    }
+    for write in analysis.self_modifying_writes() {
+        eprintln!(
+            "{}: writes to {}, part of the instruction at {}",
+            write.address, write.target, write.instruction
+        );
+    }
+    for address in analysis.invalid() {
+        eprintln!("{}: reachable, but not a valid instruction", address);
+    }
+    for range in analysis.unreachable() {
+        eprintln!("{}..{}: unreachable", range.start, range.end);
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::{self, BufRead, Write};
+use std::{env, fs, process};
+
+use advent_of_code_2019::intcode::debugger::{Debugger, StopReason};
+use advent_of_code_2019::intcode::{read_program, save, Machine};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]          execute n instructions (default 1), ignoring breakpoints
+  c, continue          run until a breakpoint, watchpoint, halt or input is needed
+  b, break <addr>      set a breakpoint
+  d, delete <addr>     remove a breakpoint
+  w, watch <addr>      stop whenever the value at an address changes
+  u, unwatch <addr>    stop watching an address
+  i, input <v>...      queue input values
+  x, mem <addr> [n]    show n memory cells (default 1) starting at an address
+  set <addr> <v>       write a value to memory
+  ip [addr]            show or move the instruction pointer
+  rb [v]               show or change the relative base
+  r, regs              show the registers, breakpoints and watchpoints
+  o, output            show and clear the output collected so far
+  save <file>          write the machine's state to a save file
+  load <file>          restore the machine's state from a save file
+  q, quit              exit";
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-debugger <program>");
+            process::exit(2);
+        }
+    };
+    let program = match read_program(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut debugger = Debugger::new(Machine::new(program));
+    println!("{}", HELP);
+    show_position(&debugger);
+
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        if words[0] == "q" || words[0] == "quit" {
+            break;
+        }
+        if let Err(message) = run_command(&mut debugger, &words) {
+            println!("{}", message);
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(word: Option<&&str>) -> Result<T, String> {
+    let word = word.ok_or_else(|| "Missing argument, try `help`".to_owned())?;
+    word.parse()
+        .map_err(|_| format!("Can't make sense of `{}`", word))
+}
+
+fn run_command(debugger: &mut Debugger, words: &[&str]) -> Result<(), String> {
+    match words[0] {
+        "s" | "step" => {
+            let count = if words.len() > 1 {
+                parse(words.get(1))?
+            } else {
+                1
+            };
+            for _ in 0..count {
+                let reason = debugger.step().map_err(|err| err.to_string())?;
+                if reason != StopReason::Stepped {
+                    show_stop(&reason);
+                    break;
+                }
+            }
+            show_output(debugger);
+            show_position(debugger);
+        }
+        "c" | "continue" => {
+            let reason = debugger.resume().map_err(|err| err.to_string())?;
+            show_stop(&reason);
+            show_output(debugger);
+            show_position(debugger);
+        }
+        "b" | "break" => {
+            debugger.add_breakpoint(parse(words.get(1))?);
+        }
+        "d" | "delete" => {
+            if !debugger.remove_breakpoint(parse(words.get(1))?) {
+                return Err("No breakpoint there".to_owned());
+            }
+        }
+        "w" | "watch" => {
+            debugger.add_watchpoint(parse(words.get(1))?);
+        }
+        "u" | "unwatch" => {
+            if !debugger.remove_watchpoint(parse(words.get(1))?) {
+                return Err("Not watching that address".to_owned());
+            }
+        }
+        "i" | "input" => {
+            for word in &words[1..] {
+                debugger.machine_mut().push_input(parse(Some(word))?);
+            }
+        }
+        "x" | "mem" => {
+            let start: usize = parse(words.get(1))?;
+            let count: usize = if words.len() > 2 {
+                parse(words.get(2))?
+            } else {
+                1
+            };
+            let memory = debugger.machine().memory();
+            for address in start..start + count {
+                println!("{:>6}  {}", address, memory.get(address));
+            }
+        }
+        "set" => {
+            let address = parse(words.get(1))?;
+            let value = parse(words.get(2))?;
+            debugger.machine_mut().set_memory(address, value);
+        }
+        "ip" => {
+            if words.len() > 1 {
+                debugger.machine_mut().set_ip(parse(words.get(1))?);
+            }
+            show_position(debugger);
+        }
+        "rb" => {
+            if words.len() > 1 {
+                debugger
+                    .machine_mut()
+                    .set_relative_base(parse(words.get(1))?);
+            }
+            println!("rb = {}", debugger.machine().relative_base());
+        }
+        "r" | "regs" => {
+            let machine = debugger.machine();
+            println!(
+                "ip = {}, rb = {}, status = {:?}",
+                machine.ip(),
+                machine.relative_base(),
+                machine.status()
+            );
+            let input = machine
+                .pending_input()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            println!("pending input: {}", input.join(", "));
+            println!(
+                "breakpoints: {:?}",
+                debugger.breakpoints().collect::<Vec<_>>()
+            );
+            println!(
+                "watchpoints: {:?}",
+                debugger.watchpoints().collect::<Vec<_>>()
+            );
+        }
+        "o" | "output" => show_output(debugger),
+        "save" => {
+            let path = words.get(1).ok_or("Missing argument, try `help`")?;
+            let contents = save::save(&debugger.machine().snapshot());
+            fs::write(path, contents).map_err(|err| err.to_string())?;
+        }
+        "load" => {
+            let path = words.get(1).ok_or("Missing argument, try `help`")?;
+            let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+            let snapshot = save::load(&contents).map_err(|err| err.to_string())?;
+            debugger.machine_mut().restore(&snapshot);
+            show_position(debugger);
+        }
+        "h" | "help" => println!("{}", HELP),
+        command => return Err(format!("Unknown command `{}`, try `help`", command)),
+    }
+    Ok(())
+}
+
+fn show_stop(reason: &StopReason) {
+    match reason {
+        StopReason::Stepped => {}
+        StopReason::Breakpoint(address) => println!("Breakpoint at {}", address),
+        StopReason::Watchpoint { address, old, new } => {
+            println!("[{}] changed from {} to {}", address, old, new)
+        }
+        StopReason::NeedsInput => println!("Waiting for input"),
+        StopReason::Halted => println!("Halted"),
+    }
+}
+
+fn show_output(debugger: &mut Debugger) {
+    let output = debugger.take_output();
+    if !output.is_empty() {
+        let output = output
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        println!("output: {}", output.join(", "));
+    }
+}
+
+fn show_position(debugger: &Debugger) {
+    println!(
+        "{:>6}  {}",
+        debugger.machine().ip(),
+        debugger.current_instruction()
+    );
+}
@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::{env, process};
+
+use advent_of_code_2019::intcode::profiler::Profiler;
+use advent_of_code_2019::intcode::{read_program, Machine, StepResult};
+
+const USAGE: &str = "\
+Usage: intcode-profile [--folded <file>] <program> [input...]
+
+Runs the program with the given input, then prints where it spent its time. With --folded,
+also writes its sampled stacks to a file, ready for flamegraph.pl or inferno-flamegraph.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    let folded = match args.iter().position(|arg| arg == "--folded") {
+        Some(index) if index + 1 < args.len() => {
+            let path = args.remove(index + 1);
+            args.remove(index);
+            Some(path)
+        }
+        Some(_) => usage(),
+        None => None,
+    };
+    let (path, input) = match args.split_first() {
+        Some((path, input)) => match input
+            .iter()
+            .map(|n| n.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(input) => (path, input),
+            Err(_) => usage(),
+        },
+        None => usage(),
+    };
+
+    let program = match read_program(path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let mut machine = Machine::new(program);
+    machine.extend_input(input);
+    let mut profiler = Profiler::new();
+    let mut outputs = Vec::new();
+    // The report is printed even if the program fails, since it's often why it's being profiled.
+    let completed = loop {
+        match machine.run_traced(&mut profiler) {
+            Ok(StepResult::Output(value)) => outputs.push(value.to_string()),
+            Ok(StepResult::NeedsInput) => {
+                eprintln!("Program needs more input than was given");
+                break false;
+            }
+            Ok(StepResult::Halted) => break true,
+            Err(err) => {
+                eprintln!("{}", err);
+                break false;
+            }
+        }
+    };
+
+    println!("Output: {}\n", outputs.join(","));
+    print!("{}", profiler);
+
+    if let Some(folded) = folded {
+        let written = File::create(&folded).and_then(|file| profiler.write_folded(file));
+        if let Err(err) = written {
+            eprintln!("Couldn't write {}: {}", folded, err);
+            process::exit(1);
+        }
+    }
+    if !completed {
+        process::exit(1);
+    }
+}
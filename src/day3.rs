@@ -92,7 +92,7 @@ fn manhattan_distance(p1: &Point, p2: &Point) -> u32 {
 pub fn solve_1(input: &(Wire, Wire)) -> u32 {
     let (wire1, wire2) = input;
 
-    let mut current_record = std::u32::MAX;
+    let mut current_record = u32::MAX;
 
     for p1 in &wire1.0 {
         for p2 in &wire2.0 {
@@ -110,7 +110,7 @@ pub fn solve_1(input: &(Wire, Wire)) -> u32 {
 #[aoc(day3, part2)]
 pub fn solve_2(input: &(Wire, Wire)) -> u32 {
     let (wire1, wire2) = input;
-    let mut current_record = std::usize::MAX;
+    let mut current_record = usize::MAX;
 
     for (d1, p1) in wire1.0.iter().enumerate() {
         for (d2, p2) in wire2.0.iter().enumerate() {
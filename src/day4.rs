@@ -96,6 +96,7 @@ fn matches_criteria_2(input: &str) -> bool {
     has_seen_pair_without_additional
 }
 
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     #[allow(unused_imports)]
     use super::*;
@@ -1,20 +1,27 @@
 use crate::intcode::execute;
 
 #[aoc_generator(day5)]
-pub fn generate_input(input: &str) -> Vec<i32> {
+pub fn generate_input(input: &str) -> Vec<i64> {
     input.split(',').map(|n| n.parse::<_>().unwrap()).collect()
 }
 
 #[aoc(day5, part1)]
-pub fn solve_1(input: &[i32]) -> i32 {
+pub fn solve_1(input: &[i64]) -> i64 {
     let mut input = input.to_vec();
-    execute(&mut input, 1).unwrap()
+    let outputs = execute(&mut input, &[1]).unwrap();
+    let (diagnostic_code, test_results) = outputs.split_last().unwrap();
+    assert!(
+        test_results.iter().all(|r| *r == 0),
+        "Failing diagnostic tests: {:?}",
+        test_results
+    );
+    *diagnostic_code
 }
 
 #[aoc(day5, part2)]
-pub fn solve_2(input: &[i32]) -> i32 {
+pub fn solve_2(input: &[i64]) -> i64 {
     let mut input = input.to_vec();
-    execute(&mut input, 5).unwrap()
+    execute(&mut input, &[5]).unwrap()[0]
 }
 
 mod tests {
@@ -28,8 +35,8 @@ mod tests {
         let mut input = generate_input("3,9,8,9,10,9,4,9,99,-1,8");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 8).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 7).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[8]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[7]).unwrap(), vec![0]);
     }
 
     #[test]
@@ -37,8 +44,8 @@ mod tests {
         let mut input = generate_input("3,9,7,9,10,9,4,9,99,-1,8");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 5).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 9).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[5]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[9]).unwrap(), vec![0]);
     }
 
     #[test]
@@ -46,8 +53,8 @@ mod tests {
         let mut input = generate_input("3,3,1108,-1,8,3,4,3,99");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 8).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 9).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[8]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[9]).unwrap(), vec![0]);
     }
 
     #[test]
@@ -55,8 +62,8 @@ mod tests {
         let mut input = generate_input("3,3,1107,-1,8,3,4,3,99");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 5).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 9).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[5]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[9]).unwrap(), vec![0]);
     }
 
     #[test]
@@ -64,8 +71,8 @@ mod tests {
         let mut input = generate_input("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 5).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 0).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[5]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[0]).unwrap(), vec![0]);
     }
 
     #[test]
@@ -73,7 +80,7 @@ mod tests {
         let mut input = generate_input("3,3,1105,-1,9,1101,0,0,12,4,12,99,1");
         let mut input_copy = input.clone();
 
-        assert_eq!(execute(&mut input, 3).unwrap(), 1);
-        assert_eq!(execute(&mut input_copy, 0).unwrap(), 0);
+        assert_eq!(execute(&mut input, &[3]).unwrap(), vec![1]);
+        assert_eq!(execute(&mut input_copy, &[0]).unwrap(), vec![0]);
     }
 }
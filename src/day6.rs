@@ -38,19 +38,19 @@ fn add_children(node: &mut NodeMut<String>, edges: &[Edge]) {
     let children = edges.iter().filter(|e| e.parent == value);
     children.for_each(|e| {
         let mut new_node = node.append(e.child.to_owned());
-        add_children(&mut new_node, &edges);
+        add_children(&mut new_node, edges);
     });
 }
 
 fn to_trees(edges: &[Edge]) -> Vec<Tree<String>> {
-    let roots = find_roots(&edges);
+    let roots = find_roots(edges);
 
     roots
         .iter()
         .map(|root| {
             let mut tree = Tree::new(root.to_owned());
             let mut root = tree.root_mut();
-            add_children(&mut root, &edges);
+            add_children(&mut root, edges);
             tree
         })
         .collect::<Vec<_>>()
@@ -108,7 +108,7 @@ fn distance_to_child(
 
 #[aoc(day6, part2)]
 fn solve_2(input: &[Edge]) -> usize {
-    let trees = to_trees(&input);
+    let trees = to_trees(input);
     let tree = &trees[0];
 
     let you = tree.nodes().find(|n| n.value() == "YOU").unwrap();
@@ -1,361 +1,1312 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::num::ParseIntError;
+use std::path::Path;
 
-// struct Memory(Vec<i32>);
-//
-// impl Memory {
-//     fn load_register(&self, address_or_value: i32, mode: &OperationMode) -> Result<i32, ExecutionError> {
-//         match mode {
-//             OperationMode::Position => {
-//                 Ok(self.0[usize::try_from(address_or_value)?])
-//             }
-//             OperationMode::Immediate => Ok(address_or_value),
-//         }
-//     }
-// }
-
-enum OperationMode {
+pub mod amplifier;
+pub mod analysis;
+pub mod ascii;
+pub mod assembler;
+mod cell;
+pub mod debugger;
+pub mod disassembler;
+mod error;
+pub mod io;
+pub mod jit;
+mod memory;
+pub mod network;
+pub mod optimizer;
+pub mod profiler;
+pub mod save;
+mod snapshot;
+pub mod stream;
+pub mod symbolic;
+pub mod trace;
+
+pub use cell::Cell;
+pub use error::{ErrorKind, ExecutionError, ProgramError};
+pub use io::{IoDevice, QueueDevice};
+pub use memory::Memory;
+pub use snapshot::Snapshot;
+pub use trace::{Event, NoTracer, Tracer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationMode {
     Position,
     Immediate,
+    Relative,
+}
+
+impl OperationMode {
+    fn from_digit(digit: i32) -> Option<Self> {
+        match digit {
+            0 => Some(OperationMode::Position),
+            1 => Some(OperationMode::Immediate),
+            2 => Some(OperationMode::Relative),
+            _ => None,
+        }
+    }
+
+    fn digit(self) -> i32 {
+        match self {
+            OperationMode::Position => 0,
+            OperationMode::Immediate => 1,
+            OperationMode::Relative => 2,
+        }
+    }
 }
 
-enum IntCode {
-    Add(OperationMode, OperationMode),
-    Multiply(OperationMode, OperationMode),
-    StoreInput,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntCode {
+    Add(OperationMode, OperationMode, OperationMode),
+    Multiply(OperationMode, OperationMode, OperationMode),
+    StoreInput(OperationMode),
     LoadOutput(OperationMode),
     JumpIfTrue(OperationMode, OperationMode),
     JumpIfFalse(OperationMode, OperationMode),
-    LessThan(OperationMode, OperationMode),
-    Equals(OperationMode, OperationMode),
+    LessThan(OperationMode, OperationMode, OperationMode),
+    Equals(OperationMode, OperationMode, OperationMode),
+    AdjustRelativeBase(OperationMode),
     Halt,
 }
 
 impl IntCode {
-    fn instruction_width(&self) -> usize {
+    pub fn instruction_width(&self) -> usize {
         match self {
-            Self::Add(_, _) => 4,
-            Self::Multiply(_, _) => 4,
-            Self::StoreInput => 2,
+            Self::Add(_, _, _) => 4,
+            Self::Multiply(_, _, _) => 4,
+            Self::StoreInput(_) => 2,
             Self::LoadOutput(_) => 2,
             Self::JumpIfTrue(_, _) => 3,
             Self::JumpIfFalse(_, _) => 3,
-            Self::LessThan(_, _) => 4,
-            Self::Equals(_, _) => 4,
+            Self::LessThan(_, _, _) => 4,
+            Self::Equals(_, _, _) => 4,
+            Self::AdjustRelativeBase(_) => 2,
             Self::Halt => 1,
         }
     }
+
+    pub fn opcode(&self) -> i32 {
+        match self {
+            Self::Add(_, _, _) => 1,
+            Self::Multiply(_, _, _) => 2,
+            Self::StoreInput(_) => 3,
+            Self::LoadOutput(_) => 4,
+            Self::JumpIfTrue(_, _) => 5,
+            Self::JumpIfFalse(_, _) => 6,
+            Self::LessThan(_, _, _) => 7,
+            Self::Equals(_, _, _) => 8,
+            Self::AdjustRelativeBase(_) => 9,
+            Self::Halt => 99,
+        }
+    }
+
+    /// The modes of the instruction's parameters, in the order they appear in memory.
+    pub fn modes(&self) -> Vec<OperationMode> {
+        match *self {
+            Self::Add(a, b, c)
+            | Self::Multiply(a, b, c)
+            | Self::LessThan(a, b, c)
+            | Self::Equals(a, b, c) => vec![a, b, c],
+            Self::JumpIfTrue(a, b) | Self::JumpIfFalse(a, b) => vec![a, b],
+            Self::StoreInput(a) | Self::LoadOutput(a) | Self::AdjustRelativeBase(a) => vec![a],
+            Self::Halt => vec![],
+        }
+    }
+
+    /// Turns the instruction back into the word it decodes from.
+    pub fn encode(&self) -> i32 {
+        self.modes()
+            .iter()
+            .rev()
+            .fold(0, |digits, mode| digits * 10 + mode.digit())
+            * 100
+            + self.opcode()
+    }
+
+    /// The short name used for the instruction in listings.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Add(_, _, _) => "ADD",
+            Self::Multiply(_, _, _) => "MUL",
+            Self::StoreInput(_) => "IN",
+            Self::LoadOutput(_) => "OUT",
+            Self::JumpIfTrue(_, _) => "JT",
+            Self::JumpIfFalse(_, _) => "JF",
+            Self::LessThan(_, _, _) => "LT",
+            Self::Equals(_, _, _) => "EQ",
+            Self::AdjustRelativeBase(_) => "ARB",
+            Self::Halt => "HLT",
+        }
+    }
+
+    /// Whether the instruction's last parameter is an address it writes its result to.
+    pub fn writes_to_memory(&self) -> bool {
+        matches!(
+            self,
+            Self::Add(_, _, _)
+                | Self::Multiply(_, _, _)
+                | Self::StoreInput(_)
+                | Self::LessThan(_, _, _)
+                | Self::Equals(_, _, _)
+        )
+    }
+}
+
+/// Why a word could not be decoded into an `IntCode`.
+///
+/// Parameters are numbered from 1, matching the order they follow the instruction in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    NegativeWord {
+        word: i32,
+    },
+    UnknownOpcode {
+        word: i32,
+        opcode: i32,
+    },
+    InvalidMode {
+        word: i32,
+        parameter: usize,
+        digit: i32,
+    },
+    ImmediateWrite {
+        word: i32,
+        parameter: usize,
+    },
+    UnusedMode {
+        word: i32,
+        parameter: usize,
+        digit: i32,
+    },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::NegativeWord { word } => write!(f, "Negative instruction word {}", word),
+            DecodeError::UnknownOpcode { word, opcode } => {
+                write!(f, "Unknown opcode {} in instruction word {}", opcode, word)
+            }
+            DecodeError::InvalidMode {
+                word,
+                parameter,
+                digit,
+            } => write!(
+                f,
+                "Invalid mode digit {} for parameter {} of instruction word {}",
+                digit, parameter, word
+            ),
+            DecodeError::ImmediateWrite { word, parameter } => write!(
+                f,
+                "Parameter {} of instruction word {} is written to but in immediate mode",
+                parameter, word
+            ),
+            DecodeError::UnusedMode {
+                word,
+                parameter,
+                digit,
+            } => write!(
+                f,
+                "Mode digit {} for nonexistent parameter {} of instruction word {}",
+                digit, parameter, word
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// How an instruction uses one of its parameters.
+#[derive(Clone, Copy)]
+enum Parameter {
+    Read,
+    Write,
+}
+
+/// Splits the mode digits off an instruction word, one per entry in `parameters`, checking that
+/// each digit is a valid mode for its parameter and that no digits are left over.
+fn decode_modes<const N: usize>(
+    word: i32,
+    parameters: [Parameter; N],
+) -> Result<[OperationMode; N], DecodeError> {
+    let mut digits = word / 100;
+    let mut modes = [OperationMode::Position; N];
+
+    for (index, (mode, parameter)) in modes.iter_mut().zip(parameters.iter()).enumerate() {
+        let digit = digits % 10;
+        digits /= 10;
+
+        *mode = OperationMode::from_digit(digit).ok_or(DecodeError::InvalidMode {
+            word,
+            parameter: index + 1,
+            digit,
+        })?;
+
+        if let (Parameter::Write, OperationMode::Immediate) = (parameter, *mode) {
+            return Err(DecodeError::ImmediateWrite {
+                word,
+                parameter: index + 1,
+            });
+        }
+    }
+
+    let mut parameter = N;
+    while digits != 0 {
+        parameter += 1;
+        if digits % 10 != 0 {
+            return Err(DecodeError::UnusedMode {
+                word,
+                parameter,
+                digit: digits % 10,
+            });
+        }
+        digits /= 10;
+    }
+
+    Ok(modes)
 }
 
 impl TryFrom<i32> for IntCode {
-    type Error = String;
+    type Error = DecodeError;
 
     fn try_from(value: i32) -> Result<Self, Self::Error> {
-        match value {
-            1 => Ok(IntCode::Add(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            101 => Ok(IntCode::Add(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1001 => Ok(IntCode::Add(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1101 => Ok(IntCode::Add(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            2 => Ok(IntCode::Multiply(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            102 => Ok(IntCode::Multiply(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1002 => Ok(IntCode::Multiply(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1102 => Ok(IntCode::Multiply(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            3 => Ok(IntCode::StoreInput),
-
-            4 => Ok(IntCode::LoadOutput(OperationMode::Position)),
-            104 => Ok(IntCode::LoadOutput(OperationMode::Immediate)),
-
-            5 => Ok(IntCode::JumpIfTrue(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            105 => Ok(IntCode::JumpIfTrue(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1005 => Ok(IntCode::JumpIfTrue(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1105 => Ok(IntCode::JumpIfTrue(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            6 => Ok(IntCode::JumpIfFalse(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            106 => Ok(IntCode::JumpIfFalse(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1006 => Ok(IntCode::JumpIfFalse(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1106 => Ok(IntCode::JumpIfFalse(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            7 => Ok(IntCode::LessThan(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            107 => Ok(IntCode::LessThan(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1007 => Ok(IntCode::LessThan(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1107 => Ok(IntCode::LessThan(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            8 => Ok(IntCode::Equals(
-                OperationMode::Position,
-                OperationMode::Position,
-            )),
-            108 => Ok(IntCode::Equals(
-                OperationMode::Immediate,
-                OperationMode::Position,
-            )),
-            1008 => Ok(IntCode::Equals(
-                OperationMode::Position,
-                OperationMode::Immediate,
-            )),
-            1108 => Ok(IntCode::Equals(
-                OperationMode::Immediate,
-                OperationMode::Immediate,
-            )),
-
-            99 => Ok(IntCode::Halt),
-            _ => Err(format!("Invalid IntCode {}", value)),
+        use Parameter::{Read, Write};
+
+        if value < 0 {
+            return Err(DecodeError::NegativeWord { word: value });
         }
+
+        Ok(match value % 100 {
+            1 => {
+                let [a, b, c] = decode_modes(value, [Read, Read, Write])?;
+                IntCode::Add(a, b, c)
+            }
+            2 => {
+                let [a, b, c] = decode_modes(value, [Read, Read, Write])?;
+                IntCode::Multiply(a, b, c)
+            }
+            3 => {
+                let [a] = decode_modes(value, [Write])?;
+                IntCode::StoreInput(a)
+            }
+            4 => {
+                let [a] = decode_modes(value, [Read])?;
+                IntCode::LoadOutput(a)
+            }
+            5 => {
+                let [a, b] = decode_modes(value, [Read, Read])?;
+                IntCode::JumpIfTrue(a, b)
+            }
+            6 => {
+                let [a, b] = decode_modes(value, [Read, Read])?;
+                IntCode::JumpIfFalse(a, b)
+            }
+            7 => {
+                let [a, b, c] = decode_modes(value, [Read, Read, Write])?;
+                IntCode::LessThan(a, b, c)
+            }
+            8 => {
+                let [a, b, c] = decode_modes(value, [Read, Read, Write])?;
+                IntCode::Equals(a, b, c)
+            }
+            9 => {
+                let [a] = decode_modes(value, [Read])?;
+                IntCode::AdjustRelativeBase(a)
+            }
+            99 => {
+                let [] = decode_modes(value, [])?;
+                IntCode::Halt
+            }
+            opcode => {
+                return Err(DecodeError::UnknownOpcode {
+                    word: value,
+                    opcode,
+                })
+            }
+        })
     }
 }
 
-#[derive(Debug)]
-pub enum ExecutionError {
-    Conversion,
-    OutOfBounds,
-    MalfunctioningInstruction,
+/// How many of the most recently executed instructions a `Machine` remembers for error reports.
+const RECENT_INSTRUCTIONS: usize = 8;
+
+/// An `ErrorKind` raised partway through an instruction, before the machine has attached the
+/// context that turns it into an `ExecutionError`.
+struct Fault {
+    kind: ErrorKind,
+    parameter: Option<usize>,
 }
 
-impl From<std::num::TryFromIntError> for ExecutionError {
-    fn from(_err: std::num::TryFromIntError) -> ExecutionError {
-        ExecutionError::Conversion
+impl Fault {
+    fn at(parameter: usize) -> impl Fn(ErrorKind) -> Fault {
+        move |kind| Fault {
+            kind,
+            parameter: Some(parameter),
+        }
+    }
+}
+
+impl From<ErrorKind> for Fault {
+    fn from(kind: ErrorKind) -> Fault {
+        Fault {
+            kind,
+            parameter: None,
+        }
     }
 }
 
-fn load_register(
-    input: &[i32],
-    address_or_value: i32,
-    mode: &OperationMode,
-) -> Result<i32, ExecutionError> {
-    match mode {
-        OperationMode::Position => Ok(input[usize::try_from(address_or_value)?]),
-        OperationMode::Immediate => Ok(address_or_value),
+impl From<DecodeError> for Fault {
+    fn from(err: DecodeError) -> Fault {
+        Fault::from(ErrorKind::from(err))
     }
 }
 
-pub fn execute(memory: &mut Vec<i32>, input: i32) -> Result<i32, ExecutionError> {
-    let mut idx = 0;
+impl From<std::num::TryFromIntError> for Fault {
+    fn from(err: std::num::TryFromIntError) -> Fault {
+        Fault::from(ErrorKind::from(err))
+    }
+}
 
-    let mut output = 0;
+/// Where a `Machine` currently is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    AwaitingInput,
+    Halted,
+}
 
-    loop {
-        let current_instruction = IntCode::try_from(memory[idx]).unwrap();
+/// The reason `Machine::run` handed control back to the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepResult<C = i64> {
+    NeedsInput,
+    Output(C),
+    Halted,
+}
 
-        match &current_instruction {
-            IntCode::Add(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+/// Guards against programs that never halt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// How many instructions the machine may execute before failing with
+    /// `ErrorKind::StepLimitExceeded`.
+    pub step_limit: Option<u64>,
+    /// Whether to fail with `ErrorKind::InfiniteLoop` as soon as the machine gets back into a
+    /// state it has been in before, without having done any I/O in between.
+    ///
+    /// This hashes all of memory before every instruction, so it's much slower than running
+    /// without it. Only the last 10,000 or so states are remembered, so longer loops are left to
+    /// the step limit.
+    pub detect_loops: bool,
+}
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                let b = load_register(
-                    &memory,
-                    *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                    b_mode,
-                )?;
-                let c_loc =
-                    usize::try_from(*memory.get(idx + 3).ok_or(ExecutionError::OutOfBounds)?)?;
-
-                memory[c_loc] = a + b;
-                idx += current_instruction.instruction_width();
-            }
-
-            IntCode::Multiply(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+/// How many states loop detection remembers before forgetting them all and starting over.
+const SEEN_STATES_LIMIT: usize = 10_000;
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                let b = load_register(
-                    &memory,
-                    *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                    b_mode,
-                )?;
-                let c_loc =
-                    usize::try_from(*memory.get(idx + 3).ok_or(ExecutionError::OutOfBounds)?)?;
-
-                memory[c_loc] = a * b;
-                idx += current_instruction.instruction_width();
-            }
-
-            IntCode::StoreInput => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+/// A state the machine has been in, kept for loop detection. Input is left out, since loops are
+/// only looked for while none is queued.
+#[derive(Debug, Clone)]
+struct SeenState<C: Cell> {
+    ip: usize,
+    relative_base: C,
+    memory: Memory<C>,
+    step: u64,
+}
 
-                let a_loc =
-                    usize::try_from(*memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?)?;
-                memory[a_loc] = input;
-                idx += current_instruction.instruction_width();
+/// A resumable Intcode computer.
+///
+/// Unlike `execute`, a `Machine` keeps its memory and instruction pointer between calls to
+/// `run`, so a program can be fed input and drained of output while it is executing.
+#[derive(Debug, Clone)]
+pub struct Machine<C: Cell = i64> {
+    memory: Memory<C>,
+    ip: usize,
+    relative_base: C,
+    status: Status,
+    input: VecDeque<C>,
+    recent: VecDeque<(usize, IntCode)>,
+    traced_parameters: Vec<C>,
+    traced_operands: Vec<C>,
+    traced_reads: Vec<usize>,
+    traced_writes: Vec<(usize, C)>,
+    limits: Limits,
+    /// Instructions already decoded, by address. Only the opcode word is cached, so an entry
+    /// only needs invalidating when that word is written to.
+    decoded: Vec<Option<IntCode>>,
+    cache_decoded: bool,
+    steps: u64,
+    /// The states seen since the last I/O, by hash.
+    seen_states: HashMap<u64, Vec<SeenState<C>>>,
+}
+
+impl<C: Cell> Machine<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        Machine {
+            memory: Memory::new(program),
+            ip: 0,
+            relative_base: C::zero(),
+            status: Status::Running,
+            input: VecDeque::new(),
+            recent: VecDeque::with_capacity(RECENT_INSTRUCTIONS),
+            traced_parameters: Vec::new(),
+            traced_operands: Vec::new(),
+            traced_reads: Vec::new(),
+            traced_writes: Vec::new(),
+            limits: Limits::default(),
+            decoded: Vec::new(),
+            cache_decoded: true,
+            steps: 0,
+            seen_states: HashMap::new(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Turns caching of decoded instructions on or off. It's on by default, and only worth
+    /// turning off to measure how much it helps.
+    pub fn with_decode_cache(mut self, enabled: bool) -> Self {
+        self.cache_decoded = enabled;
+        self.decoded.clear();
+        self
+    }
+
+    /// How many instructions the machine has executed.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    /// Gives write access to memory. Any instructions decoded so far are forgotten, since we
+    /// can't know which of them the caller is going to overwrite.
+    pub fn memory_mut(&mut self) -> &mut Memory<C> {
+        self.decoded.clear();
+        &mut self.memory
+    }
+
+    pub fn into_memory(self) -> Memory<C> {
+        self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// Writes a single memory cell. Prefer this to `memory_mut` between runs, since it only
+    /// forgets the decoded instruction at `index` rather than all of them.
+    pub fn set_memory(&mut self, index: usize, value: C) {
+        self.invalidate_decoded(index);
+        self.memory.set(index, value);
+    }
+
+    fn invalidate_decoded(&mut self, index: usize) {
+        if let Some(decoded) = self.decoded.get_mut(index) {
+            *decoded = None;
+        }
+    }
+
+    /// Moves the instruction pointer, waking the machine if it had halted or was waiting for
+    /// input at the old address.
+    pub fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+        self.status = Status::Running;
+    }
+
+    pub fn relative_base(&self) -> &C {
+        &self.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: C) {
+        self.relative_base = relative_base;
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Queues a value for a later `StoreInput` instruction, waking the machine if it was
+    /// blocked waiting for one.
+    pub fn push_input(&mut self, value: C) {
+        self.input.push_back(value);
+        self.seen_states.clear();
+        if self.status == Status::AwaitingInput {
+            self.status = Status::Running;
+        }
+    }
+
+    /// The queued input values that haven't been consumed yet, oldest first.
+    pub fn pending_input(&self) -> impl Iterator<Item = &C> {
+        self.input.iter()
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = C>>(&mut self, values: I) {
+        for value in values {
+            self.push_input(value);
+        }
+    }
+
+    /// Runs the program until it halts, collecting every value it outputs along the way.
+    pub fn run_to_completion(&mut self) -> Result<Vec<C>, ExecutionError> {
+        let mut device = QueueDevice::new(Vec::new());
+        match self.run_with(&mut device)? {
+            Status::Halted => Ok(device.into_output()),
+            _ => Err(self.error(self.ip, ErrorKind::MissingInput.into())),
+        }
+    }
+
+    fn error(&self, ip: usize, fault: Fault) -> ExecutionError {
+        ExecutionError::new(
+            fault.kind,
+            ip,
+            self.memory.get(ip).to_i64(),
+            fault.parameter,
+            self.recent.iter().cloned().collect(),
+        )
+    }
+
+    fn parameter(&self, offset: usize) -> C {
+        self.memory.get(self.ip + offset)
+    }
+
+    /// Resolves the address a position or relative mode parameter points at.
+    fn address(&self, offset: usize, mode: &OperationMode) -> Result<C, Fault> {
+        let parameter = self.parameter(offset);
+        match mode {
+            OperationMode::Position => Ok(parameter),
+            OperationMode::Relative => self
+                .relative_base
+                .checked_add(&parameter)
+                .ok_or(ErrorKind::Overflow)
+                .map_err(Fault::at(offset)),
+            OperationMode::Immediate => Err(Fault::at(offset)(ErrorKind::Conversion)),
+        }
+    }
+
+    fn operand(&self, offset: usize, mode: &OperationMode) -> Result<C, Fault> {
+        match mode {
+            OperationMode::Immediate => Ok(self.parameter(offset)),
+            _ => self
+                .memory
+                .read(&self.address(offset, mode)?)
+                .map_err(Fault::at(offset)),
+        }
+    }
+
+    /// Reads an operand, noting it down for the tracer if it wants to know.
+    fn read<T: Tracer<C>>(&mut self, offset: usize, mode: &OperationMode) -> Result<C, Fault> {
+        let value = self.operand(offset, mode)?;
+        if T::ENABLED {
+            if *mode != OperationMode::Immediate {
+                let address = self.address(offset, mode)?;
+                let index = Memory::index(&address).map_err(Fault::at(offset))?;
+                self.traced_reads.push(index);
             }
+            self.traced_operands.push(value.clone());
+        }
+        Ok(value)
+    }
 
-            IntCode::LoadOutput(a_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+    fn store<T: Tracer<C>>(
+        &mut self,
+        offset: usize,
+        mode: &OperationMode,
+        value: C,
+    ) -> Result<(), Fault> {
+        let address = self.address(offset, mode)?;
+        let index = Memory::index(&address).map_err(Fault::at(offset))?;
+        if T::ENABLED {
+            self.traced_writes.push((index, value.clone()));
+        }
+        self.invalidate_decoded(index);
+        self.memory.set(index, value);
+        Ok(())
+    }
+
+    fn jump_target<T: Tracer<C>>(
+        &mut self,
+        offset: usize,
+        mode: &OperationMode,
+    ) -> Result<usize, Fault> {
+        Memory::index(&self.read::<T>(offset, mode)?).map_err(Fault::at(offset))
+    }
+
+    fn flag(condition: bool) -> C {
+        if condition {
+            C::one()
+        } else {
+            C::zero()
+        }
+    }
+
+    /// Executes instructions until the program produces output, blocks on input or halts.
+    pub fn run(&mut self) -> Result<StepResult<C>, ExecutionError> {
+        self.run_traced(&mut NoTracer)
+    }
 
-                output = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                idx += current_instruction.instruction_width();
+    /// Like `run`, but reports every instruction executed to `tracer`.
+    pub fn run_traced<T: Tracer<C>>(
+        &mut self,
+        tracer: &mut T,
+    ) -> Result<StepResult<C>, ExecutionError> {
+        loop {
+            if let Some(result) = self.step_traced(tracer)? {
+                return Ok(result);
             }
+        }
+    }
 
-            IntCode::JumpIfTrue(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+    /// Executes a single instruction, returning why the machine stopped if it can't carry on
+    /// without the caller.
+    pub fn step(&mut self) -> Result<Option<StepResult<C>>, ExecutionError> {
+        self.step_traced(&mut NoTracer)
+    }
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                if a != 0 {
-                    let b = usize::try_from(load_register(
-                        &memory,
-                        *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                        b_mode,
-                    )?)?;
-                    idx = b;
-                } else {
-                    idx += current_instruction.instruction_width();
-                }
+    /// Like `step`, but reports the instruction executed to `tracer`.
+    pub fn step_traced<T: Tracer<C>>(
+        &mut self,
+        tracer: &mut T,
+    ) -> Result<Option<StepResult<C>>, ExecutionError> {
+        match self.status {
+            Status::Halted => return Ok(Some(StepResult::Halted)),
+            Status::AwaitingInput if self.input.is_empty() => {
+                return Ok(Some(StepResult::NeedsInput))
             }
+            _ => {}
+        }
 
-            IntCode::JumpIfFalse(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+        let ip = self.ip;
+        if let Some(limit) = self.limits.step_limit {
+            if self.steps >= limit {
+                return Err(self.error(ip, ErrorKind::StepLimitExceeded { limit }.into()));
+            }
+        }
+        if self.limits.detect_loops && self.input.is_empty() {
+            if let Some(first_seen) = self.revisited_state() {
+                let period = self.steps - first_seen;
+                return Err(self.error(ip, ErrorKind::InfiniteLoop { period }.into()));
+            }
+        }
+
+        let result = self
+            .execute_instruction(tracer)
+            .map_err(|fault| self.error(ip, fault))?;
+        match result {
+            Some(StepResult::NeedsInput) => {}
+            Some(StepResult::Output(_)) => {
+                self.steps += 1;
+                self.seen_states.clear();
+            }
+            _ => self.steps += 1,
+        }
+        Ok(result)
+    }
+
+    /// Remembers the current state, returning the step the machine was last in it at if it has
+    /// been in it before.
+    fn revisited_state(&mut self) -> Option<u64> {
+        if self.seen_states.len() >= SEEN_STATES_LIMIT {
+            self.seen_states.clear();
+        }
+        let hash = self.state_hash();
+        let (ip, relative_base, memory) = (self.ip, &self.relative_base, &self.memory);
+        let states = self.seen_states.entry(hash).or_default();
+        let seen = states.iter().find(|state| {
+            state.ip == ip && state.relative_base == *relative_base && state.memory == *memory
+        });
+        if let Some(state) = seen {
+            return Some(state.step);
+        }
+        states.push(SeenState {
+            ip,
+            relative_base: relative_base.clone(),
+            memory: memory.clone(),
+            step: self.steps,
+        });
+        None
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.ip.hash(&mut hasher);
+        self.relative_base.hash(&mut hasher);
+        self.memory.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn decode(&mut self) -> Result<IntCode, Fault> {
+        if let Some(Some(instruction)) = self.decoded.get(self.ip) {
+            return Ok(*instruction);
+        }
+
+        // Only instructions in the dense region are cached, so that running code at a distant
+        // address doesn't grow the cache all the way out to it.
+        let instruction = self.decode_uncached()?;
+        if self.cache_decoded && self.memory.is_dense(self.ip) {
+            if self.decoded.len() <= self.ip {
+                self.decoded.resize(self.ip + 1, None);
+            }
+            self.decoded[self.ip] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    fn decode_uncached(&self) -> Result<IntCode, Fault> {
+        let word = self
+            .memory
+            .get(self.ip)
+            .to_i64()
+            .ok_or(ErrorKind::Conversion)?;
+        Ok(IntCode::try_from(i32::try_from(word)?)?)
+    }
+
+    /// Adds an instruction about to be executed to the ones listed in error reports.
+    fn remember(&mut self, ip: usize, instruction: IntCode) {
+        if self.recent.len() == RECENT_INSTRUCTIONS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((ip, instruction));
+    }
+
+    fn execute_instruction<T: Tracer<C>>(
+        &mut self,
+        tracer: &mut T,
+    ) -> Result<Option<StepResult<C>>, Fault> {
+        let current_instruction = self.decode()?;
+
+        if let IntCode::StoreInput(_) = current_instruction {
+            if self.input.is_empty() {
+                self.status = Status::AwaitingInput;
+                return Ok(Some(StepResult::NeedsInput));
+            }
+        }
+
+        self.remember(self.ip, current_instruction);
+
+        let ip = self.ip;
+        tracer.before(ip, &current_instruction);
+        if T::ENABLED {
+            self.traced_parameters.clear();
+            self.traced_operands.clear();
+            self.traced_reads.clear();
+            self.traced_writes.clear();
+            for offset in 1..current_instruction.instruction_width() {
+                let parameter = self.parameter(offset);
+                self.traced_parameters.push(parameter);
+            }
+        }
+
+        let result = self.execute_decoded::<T>(&current_instruction)?;
+
+        tracer.after(&Event {
+            ip,
+            instruction: current_instruction,
+            parameters: &self.traced_parameters,
+            operands: &self.traced_operands,
+            reads: &self.traced_reads,
+            writes: &self.traced_writes,
+            next_ip: self.ip,
+        });
+
+        Ok(result)
+    }
+
+    fn execute_decoded<T: Tracer<C>>(
+        &mut self,
+        current_instruction: &IntCode,
+    ) -> Result<Option<StepResult<C>>, Fault> {
+        match current_instruction {
+            IntCode::Add(a_mode, b_mode, c_mode) => {
+                let a = self.read::<T>(1, a_mode)?;
+                let b = self.read::<T>(2, b_mode)?;
+                let sum = a.checked_add(&b).ok_or(ErrorKind::Overflow)?;
+                self.store::<T>(3, c_mode, sum)?;
+            }
+
+            IntCode::Multiply(a_mode, b_mode, c_mode) => {
+                let a = self.read::<T>(1, a_mode)?;
+                let b = self.read::<T>(2, b_mode)?;
+                let product = a.checked_mul(&b).ok_or(ErrorKind::Overflow)?;
+                self.store::<T>(3, c_mode, product)?;
+            }
+
+            IntCode::StoreInput(a_mode) => {
+                let value = self
+                    .input
+                    .pop_front()
+                    .expect("Blocked input should have been handled before executing");
+                self.store::<T>(1, a_mode, value)?;
+            }
+
+            IntCode::LoadOutput(a_mode) => {
+                let a = self.read::<T>(1, a_mode)?;
+                self.ip += current_instruction.instruction_width();
+                return Ok(Some(StepResult::Output(a)));
+            }
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                if a == 0 {
-                    let b = usize::try_from(load_register(
-                        &memory,
-                        *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                        b_mode,
-                    )?)?;
-                    idx = b;
-                } else {
-                    idx += current_instruction.instruction_width();
+            IntCode::JumpIfTrue(a_mode, b_mode) => {
+                if !self.read::<T>(1, a_mode)?.is_zero() {
+                    self.ip = self.jump_target::<T>(2, b_mode)?;
+                    return Ok(None);
                 }
             }
 
-            IntCode::LessThan(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
+            IntCode::JumpIfFalse(a_mode, b_mode) => {
+                if self.read::<T>(1, a_mode)?.is_zero() {
+                    self.ip = self.jump_target::<T>(2, b_mode)?;
+                    return Ok(None);
                 }
+            }
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                let b = load_register(
-                    &memory,
-                    *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                    b_mode,
-                )?;
-                let c_loc =
-                    usize::try_from(*memory.get(idx + 3).ok_or(ExecutionError::OutOfBounds)?)?;
+            IntCode::LessThan(a_mode, b_mode, c_mode) => {
+                let a = self.read::<T>(1, a_mode)?;
+                let b = self.read::<T>(2, b_mode)?;
+                self.store::<T>(3, c_mode, Self::flag(a < b))?;
+            }
 
-                memory[c_loc] = if a < b { 1 } else { 0 };
+            IntCode::Equals(a_mode, b_mode, c_mode) => {
+                let a = self.read::<T>(1, a_mode)?;
+                let b = self.read::<T>(2, b_mode)?;
+                self.store::<T>(3, c_mode, Self::flag(a == b))?;
+            }
 
-                idx += current_instruction.instruction_width();
+            IntCode::AdjustRelativeBase(a_mode) => {
+                let offset = self.read::<T>(1, a_mode)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(&offset)
+                    .ok_or(ErrorKind::Overflow)
+                    .map_err(Fault::at(1))?;
             }
 
-            IntCode::Equals(a_mode, b_mode) => {
-                if output != 0 {
-                    return Err(ExecutionError::MalfunctioningInstruction);
-                }
+            IntCode::Halt => {
+                self.status = Status::Halted;
+                return Ok(Some(StepResult::Halted));
+            }
+        }
+
+        self.ip += current_instruction.instruction_width();
+        Ok(None)
+    }
+}
+
+/// Parses a program written as comma-separated integers, as puzzle inputs are.
+pub fn parse_program(source: &str) -> Result<Vec<i64>, ParseIntError> {
+    source.trim().split(',').map(|n| n.trim().parse()).collect()
+}
+
+/// Reads a program from a file in the format `parse_program` expects.
+pub fn read_program<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, ProgramError> {
+    let path = path.as_ref();
+    let source =
+        std::fs::read_to_string(path).map_err(|err| ProgramError::Io(path.to_owned(), err))?;
+    parse_program(&source).map_err(|err| ProgramError::Parse(path.to_owned(), err))
+}
+
+/// Runs a program to completion, feeding it `inputs` in order and returning everything it
+/// outputs.
+pub fn execute<C: Cell>(memory: &mut Vec<C>, inputs: &[C]) -> Result<Vec<C>, ExecutionError> {
+    execute_with_limits(memory, inputs, Limits::default())
+}
+
+/// Like `execute`, but gives up on programs that run for too long or loop forever.
+pub fn execute_with_limits<C: Cell>(
+    memory: &mut Vec<C>,
+    inputs: &[C],
+    limits: Limits,
+) -> Result<Vec<C>, ExecutionError> {
+    let mut machine = Machine::new(std::mem::take(memory)).with_limits(limits);
+    let mut device = QueueDevice::new(inputs.iter().cloned());
+
+    let result = match machine.run_with(&mut device) {
+        Ok(Status::Halted) => Ok(device.into_output()),
+        Ok(_) => Err(machine.error(machine.ip, ErrorKind::MissingInput.into())),
+        Err(err) => Err(err),
+    };
+
+    flatten_memory(machine, memory, result)
+}
+
+/// Hands the memory of a finished `execute` back to the caller. If it can't be flattened,
+/// `memory` is left empty, and the result is replaced by the error unless it already is one.
+fn flatten_memory<C: Cell>(
+    machine: Machine<C>,
+    memory: &mut Vec<C>,
+    result: Result<Vec<C>, ExecutionError>,
+) -> Result<Vec<C>, ExecutionError> {
+    match machine.memory.to_vec() {
+        Ok(flattened) => {
+            *memory = flattened;
+            result
+        }
+        Err(kind) => result.and(Err(machine.error(machine.ip, kind.into()))),
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn machine_blocks_until_input_is_provided() {
+        let mut machine = Machine::new(vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]);
 
-                let a = load_register(
-                    &memory,
-                    *memory.get(idx + 1).ok_or(ExecutionError::OutOfBounds)?,
-                    a_mode,
-                )?;
-                let b = load_register(
-                    &memory,
-                    *memory.get(idx + 2).ok_or(ExecutionError::OutOfBounds)?,
-                    b_mode,
-                )?;
-                let c_loc =
-                    usize::try_from(*memory.get(idx + 3).ok_or(ExecutionError::OutOfBounds)?)?;
+        assert_eq!(machine.run().unwrap(), StepResult::NeedsInput);
+        assert_eq!(machine.status(), Status::AwaitingInput);
+        assert_eq!(machine.run().unwrap(), StepResult::NeedsInput);
 
-                memory[c_loc] = if a == b { 1 } else { 0 };
+        machine.push_input(3);
+        assert_eq!(machine.run().unwrap(), StepResult::NeedsInput);
 
-                idx += current_instruction.instruction_width();
+        machine.push_input(4);
+        assert_eq!(machine.run().unwrap(), StepResult::Output(7));
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+        assert_eq!(machine.status(), Status::Halted);
+    }
+
+    #[test]
+    fn machine_pauses_on_every_output() {
+        let mut machine = Machine::new(vec![104, 1, 104, 2, 99]);
+
+        assert_eq!(machine.run().unwrap(), StepResult::Output(1));
+        assert_eq!(machine.ip(), 2);
+        assert_eq!(machine.run().unwrap(), StepResult::Output(2));
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn machine_consumes_queued_inputs_in_order() {
+        let mut machine = Machine::new(vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]);
+        machine.extend_input(vec![3, 4]);
+
+        assert_eq!(machine.run_to_completion().unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn it_parses_programs() {
+        assert_eq!(parse_program("1,9, 10,-3\n"), Ok(vec![1, 9, 10, -3]));
+        assert!(parse_program("1,,2").is_err());
+    }
+
+    #[test]
+    fn execute_collects_every_output() {
+        let mut memory = vec![3, 9, 4, 9, 104, 5, 4, 9, 99, 0];
+
+        assert_eq!(execute(&mut memory, &[2]).unwrap(), vec![2, 5, 2]);
+    }
+
+    #[test]
+    fn execute_reports_exhausted_input() {
+        let mut memory = vec![3, 5, 3, 5, 99, 0];
+
+        let err = execute(&mut memory, &[1]).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::MissingInput);
+        assert_eq!(err.ip(), 2);
+    }
+
+    #[test]
+    fn execute_refuses_to_flatten_distant_writes() {
+        let mut memory = vec![21101_i64, 1, 1, 1_000_000_000_000, 99];
+
+        let err = execute(&mut memory, &[]).unwrap_err();
+
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::MemoryTooLarge {
+                address: 1_000_000_000_000
             }
+        );
+        assert!(memory.is_empty());
+    }
+
+    #[test]
+    fn relative_mode_reads_from_the_relative_base() {
+        let mut memory = vec![109, 7, 204, -1, 99, 0, 42];
+
+        assert_eq!(execute(&mut memory, &[]).unwrap(), vec![42]);
+    }
+
+    #[test]
+    fn relative_mode_writes_to_the_relative_base() {
+        let mut memory = vec![109, 4, 9, 1, 203, 2, 204, 2, 99, 0, 0];
+
+        assert_eq!(execute(&mut memory, &[13]).unwrap(), vec![13]);
+        assert_eq!(memory[10], 13);
+    }
+
+    #[test]
+    fn write_parameters_are_never_immediate() {
+        assert_eq!(
+            IntCode::try_from(11101),
+            Err(DecodeError::ImmediateWrite {
+                word: 11101,
+                parameter: 3
+            })
+        );
+        assert_eq!(
+            IntCode::try_from(103),
+            Err(DecodeError::ImmediateWrite {
+                word: 103,
+                parameter: 1
+            })
+        );
+        assert!(IntCode::try_from(21101).is_ok());
+        assert!(IntCode::try_from(203).is_ok());
+    }
+
+    #[test]
+    fn decode_errors_name_the_offending_digit() {
+        assert_eq!(
+            IntCode::try_from(3101),
+            Err(DecodeError::InvalidMode {
+                word: 3101,
+                parameter: 2,
+                digit: 3
+            })
+        );
+        assert_eq!(
+            IntCode::try_from(10004),
+            Err(DecodeError::UnusedMode {
+                word: 10004,
+                parameter: 3,
+                digit: 1
+            })
+        );
+        assert_eq!(
+            IntCode::try_from(42),
+            Err(DecodeError::UnknownOpcode {
+                word: 42,
+                opcode: 42
+            })
+        );
+    }
+
+    #[test]
+    fn machine_handles_64_bit_values() {
+        let mut memory: Vec<i64> = vec![1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0];
+
+        assert_eq!(
+            execute(&mut memory, &[]).unwrap(),
+            vec![1_219_070_632_396_864]
+        );
+    }
+
+    #[test]
+    fn arithmetic_overflow_is_reported() {
+        let mut memory: Vec<i32> = vec![1102, 65_536, 65_536, 5, 99, 0];
+
+        assert_eq!(
+            execute(&mut memory, &[]).unwrap_err().kind(),
+            &ErrorKind::Overflow
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn machine_handles_arbitrary_precision_values() {
+        use num_bigint::BigInt;
 
-            IntCode::Halt => return Ok(output),
+        let mut memory = vec![1102, i64::MAX, i64::MAX, 7, 4, 7, 99, 0]
+            .into_iter()
+            .map(BigInt::from)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            execute(&mut memory, &[]).unwrap(),
+            vec![BigInt::from(i64::MAX) * BigInt::from(i64::MAX)]
+        );
+    }
+
+    #[test]
+    fn memory_grows_beyond_the_program() {
+        let mut memory = vec![109, 1, 21101, 3, 4, 100, 204, 100, 99];
+
+        assert_eq!(execute(&mut memory, &[]).unwrap(), vec![7]);
+        assert_eq!(memory.len(), 102);
+        assert_eq!(memory[101], 7);
+    }
+
+    #[test]
+    fn negative_addresses_are_reported() {
+        let mut memory = vec![4, -1, 99];
+
+        let err = execute(&mut memory, &[]).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::NegativeAddress);
+        assert_eq!(err.parameter(), Some(1));
+    }
+
+    #[test]
+    fn relative_mode_program_outputs_a_copy_of_itself() {
+        let program = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut memory = program.clone();
+
+        assert_eq!(execute(&mut memory, &[]).unwrap(), program);
+    }
+
+    #[test]
+    fn unknown_opcodes_are_reported_with_context() {
+        let mut memory = vec![1101, 1, 2, 5, 1106, 0, 7, 42, 99];
+
+        let err = execute(&mut memory, &[]).unwrap_err();
+
+        assert_eq!(
+            err.kind(),
+            &ErrorKind::Decode(DecodeError::UnknownOpcode {
+                word: 42,
+                opcode: 42
+            })
+        );
+        assert_eq!(err.ip(), 7);
+        assert_eq!(err.word(), Some(42));
+        assert_eq!(
+            err.recent_instructions(),
+            &[
+                (
+                    0,
+                    IntCode::Add(
+                        OperationMode::Immediate,
+                        OperationMode::Immediate,
+                        OperationMode::Position
+                    )
+                ),
+                (
+                    4,
+                    IntCode::JumpIfFalse(OperationMode::Immediate, OperationMode::Immediate)
+                ),
+            ]
+        );
+        assert!(err
+            .to_string()
+            .starts_with("Unknown opcode 42 in instruction word 42 at address 7\n"));
+    }
+
+    #[test]
+    fn it_gives_up_after_the_step_limit() {
+        let mut program = vec![1105, 1, 0];
+        let err = execute_with_limits(
+            &mut program,
+            &[],
+            Limits {
+                step_limit: Some(100),
+                ..Limits::default()
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::StepLimitExceeded { limit: 100 });
+        assert_eq!(err.ip(), 0);
+    }
+
+    #[test]
+    fn it_detects_programs_stuck_in_a_loop() {
+        // Only the first instruction is outside the loop, and the loop itself changes nothing.
+        let mut program = vec![1101, 1, 1, 11, 1101, 0, 0, 12, 1105, 1, 4, 0, 0];
+        let err = execute_with_limits(
+            &mut program,
+            &[],
+            Limits {
+                detect_loops: true,
+                ..Limits::default()
+            },
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::InfiniteLoop { period: 2 });
+        assert_eq!(
+            err.to_string().lines().next().unwrap(),
+            "Program is stuck in a loop, repeating every 2 steps at address 4 (instruction word 1101)"
+        );
+    }
+
+    #[test]
+    fn states_that_only_share_a_hash_are_not_a_loop() {
+        let mut machine = Machine::new(vec![1101, 2, 3, 5, 99, 0]).with_limits(Limits {
+            detect_loops: true,
+            ..Limits::default()
+        });
+        let collision = SeenState {
+            ip: 0,
+            relative_base: 0,
+            memory: Memory::new(vec![99]),
+            step: 0,
+        };
+        machine
+            .seen_states
+            .insert(machine.state_hash(), vec![collision]);
+
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn loop_detection_forgets_old_states() {
+        // Counts to 20000 without doing any I/O.
+        let program = vec![1001, 12, 1, 12, 1007, 12, 20000, 13, 1005, 13, 0, 99, 0, 0];
+        let mut machine = Machine::new(program).with_limits(Limits {
+            detect_loops: true,
+            ..Limits::default()
+        });
+
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+        assert_eq!(machine.steps(), 60_001);
+        assert!(machine.seen_states.len() <= SEEN_STATES_LIMIT);
+    }
+
+    #[test]
+    fn loops_that_do_io_are_not_infinite() {
+        let mut machine = Machine::new(vec![104, 5, 1105, 1, 0]).with_limits(Limits {
+            detect_loops: true,
+            ..Limits::default()
+        });
+
+        for _ in 0..10 {
+            assert_eq!(machine.run().unwrap(), StepResult::Output(5));
         }
+        assert_eq!(machine.steps(), 19);
+    }
+
+    #[test]
+    fn instructions_at_distant_addresses_run_uncached() {
+        let mut machine = Machine::new(vec![
+            1101_i64,
+            99,
+            0,
+            1_000_000_000_000,
+            1105,
+            1,
+            1_000_000_000_000,
+        ]);
+
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+        assert_eq!(machine.ip(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn writes_to_code_invalidate_decoded_instructions() {
+        // Runs the instruction at 0, replaces it with a halt and jumps back to it.
+        let program = vec![1101, 0, 0, 20, 1101, 0, 99, 0, 1105, 1, 0];
+        let mut machine = Machine::new(program).with_limits(Limits {
+            step_limit: Some(100),
+            ..Limits::default()
+        });
+        let start = machine.snapshot();
+
+        assert_eq!(machine.run_to_completion().unwrap(), vec![]);
+        assert_eq!(machine.steps(), 4);
+
+        machine.restore(&start);
+        assert_eq!(machine.decoded[0], None);
+        assert!(machine.decoded[4].is_some());
+        assert_eq!(machine.run_to_completion().unwrap(), vec![]);
+        assert_eq!(machine.steps(), 4);
     }
 }
@@ -0,0 +1,171 @@
+//! Chains of machines all running the same program, each seeded with its own phase setting and
+//! passing a signal on to the next.
+
+use super::{Cell, ErrorKind, ExecutionError, Machine, StepResult};
+
+/// How the amplifiers in a chain are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The signal passes through each amplifier once, and the last amplifier's output is the
+    /// result.
+    Serial,
+    /// The last amplifier's output is fed back into the first, round and round until they
+    /// halt. The last signal the last amplifier output is the result.
+    Feedback,
+}
+
+fn missing_output<C: Cell>(machine: &Machine<C>) -> ExecutionError {
+    machine.error(machine.ip, ErrorKind::MissingOutput.into())
+}
+
+/// Runs `signal` through one amplifier per phase setting, each receiving its phase setting
+/// followed by the signal from the previous amplifier.
+pub fn run_chain<C: Cell>(
+    program: &[C],
+    phases: &[C],
+    signal: C,
+    mode: Mode,
+) -> Result<C, ExecutionError> {
+    let mut machines = phases
+        .iter()
+        .map(|phase| {
+            let mut machine = Machine::new(program.to_vec());
+            machine.push_input(phase.clone());
+            machine
+        })
+        .collect::<Vec<_>>();
+
+    match mode {
+        Mode::Serial => machines.iter_mut().try_fold(signal, |signal, machine| {
+            machine.push_input(signal);
+            let outputs = machine.run_to_completion()?;
+            outputs
+                .into_iter()
+                .last()
+                .ok_or_else(|| missing_output(machine))
+        }),
+        Mode::Feedback => feedback_loop(&mut machines, signal),
+    }
+}
+
+fn feedback_loop<C: Cell>(machines: &mut [Machine<C>], mut signal: C) -> Result<C, ExecutionError> {
+    if machines.is_empty() {
+        return Ok(signal);
+    }
+
+    let last = machines.len() - 1;
+    let mut result = None;
+    loop {
+        for (index, machine) in machines.iter_mut().enumerate() {
+            machine.push_input(signal.clone());
+            match machine.run()? {
+                StepResult::Output(value) => {
+                    if index == last {
+                        result = Some(value.clone());
+                    }
+                    signal = value;
+                }
+                StepResult::Halted => return result.ok_or_else(|| missing_output(machine)),
+                // Nothing else will produce input while this amplifier waits for more.
+                StepResult::NeedsInput => {
+                    return Err(machine.error(machine.ip, ErrorKind::MissingInput.into()))
+                }
+            }
+        }
+    }
+}
+
+/// Every ordering of `items`, generated with Heap's algorithm.
+fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    fn generate<T: Clone>(k: usize, items: &mut Vec<T>, out: &mut Vec<Vec<T>>) {
+        if k <= 1 {
+            out.push(items.clone());
+            return;
+        }
+        for i in 0..k - 1 {
+            generate(k - 1, items, out);
+            if k.is_multiple_of(2) {
+                items.swap(i, k - 1);
+            } else {
+                items.swap(0, k - 1);
+            }
+        }
+        generate(k - 1, items, out);
+    }
+
+    let mut out = Vec::new();
+    generate(items.len(), &mut items.to_vec(), &mut out);
+    out
+}
+
+/// Tries every ordering of `phases`, starting each chain with a signal of zero, and returns the
+/// ordering that produces the largest final signal along with that signal.
+pub fn best_phases<C: Cell>(
+    program: &[C],
+    phases: &[C],
+    mode: Mode,
+) -> Result<(Vec<C>, C), ExecutionError> {
+    let mut best: Option<(Vec<C>, C)> = None;
+    for ordering in permutations(phases) {
+        let signal = run_chain(program, &ordering, C::zero(), mode)?;
+        if best.as_ref().is_none_or(|(_, best)| signal > *best) {
+            best = Some((ordering, signal));
+        }
+    }
+    Ok(best.expect("There is always at least one ordering"))
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn it_finds_the_best_serial_phases() {
+        let program = vec![
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ];
+
+        assert_eq!(
+            best_phases(&program, &[0, 1, 2, 3, 4], Mode::Serial).unwrap(),
+            (vec![0, 1, 2, 3, 4], 54321)
+        );
+    }
+
+    #[test]
+    fn it_finds_the_best_feedback_loop_phases() {
+        let program = vec![
+            3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54,
+            -5, 54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4,
+            53, 1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10,
+        ];
+
+        assert_eq!(
+            run_chain(&program, &[9, 7, 8, 5, 6], 0, Mode::Feedback).unwrap(),
+            18216
+        );
+        assert_eq!(
+            best_phases(&program, &[5, 6, 7, 8, 9], Mode::Feedback)
+                .unwrap()
+                .1,
+            18216
+        );
+    }
+
+    #[test]
+    fn permutations_cover_every_ordering_once() {
+        let mut orderings = permutations(&[1, 2, 3, 4]);
+        orderings.sort();
+        orderings.dedup();
+
+        assert_eq!(orderings.len(), 24);
+        assert_eq!(permutations::<i64>(&[]), vec![Vec::<i64>::new()]);
+    }
+
+    #[test]
+    fn an_amplifier_without_output_is_an_error() {
+        let err = run_chain(&[3, 0, 3, 0, 99], &[1, 2], 0, Mode::Serial).unwrap_err();
+
+        assert_eq!(err.kind(), &ErrorKind::MissingOutput);
+    }
+}
@@ -0,0 +1,406 @@
+//! Static analysis of Intcode programs: basic blocks, the control-flow graph between them, and
+//! the things about a program that make it hard to reason about.
+//!
+//! Code is found by following control flow from the entry point, so only jumps whose targets
+//! are immediate values can be followed. Jumps through memory are reported as indirect, and
+//! anything only reachable through them ends up counted as unreachable.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::disassembler::{decode_at, Entry};
+use super::{Cell, IntCode, Memory, OperationMode};
+
+/// A run of instructions that always execute together, from the first to the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<C: Cell = i64> {
+    start: usize,
+    end: usize,
+    instructions: Vec<Entry<C>>,
+    jump: Option<usize>,
+    fall_through: Option<usize>,
+    indirect: bool,
+}
+
+impl<C: Cell> Block<C> {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The address just past the block's last instruction.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn instructions(&self) -> &[Entry<C>] {
+        &self.instructions
+    }
+
+    /// Where the block's last instruction jumps to, if it's a jump with a known target that
+    /// can be taken.
+    pub fn jump(&self) -> Option<usize> {
+        self.jump
+    }
+
+    /// Where execution carries on if the block doesn't jump, unless it always jumps or halts.
+    pub fn fall_through(&self) -> Option<usize> {
+        self.fall_through
+    }
+
+    /// Whether the block ends in a jump whose target is read from memory.
+    pub fn is_indirect(&self) -> bool {
+        self.indirect
+    }
+
+    pub fn successors(&self) -> impl Iterator<Item = usize> {
+        self.jump.into_iter().chain(self.fall_through)
+    }
+}
+
+/// An instruction that writes to an address holding reachable code, or a reachable word that
+/// isn't a valid instruction (yet).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModifyingWrite {
+    /// The address of the writing instruction.
+    pub address: usize,
+    /// The address it writes to.
+    pub target: usize,
+    /// The start of the instruction whose words include `target`, or `target` itself if it
+    /// isn't a valid instruction.
+    pub instruction: usize,
+}
+
+/// Everything learnt about a program by `analyze`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis<C: Cell = i64> {
+    blocks: BTreeMap<usize, Block<C>>,
+    unreachable: Vec<Range<usize>>,
+    indirect_jumps: Vec<usize>,
+    self_modifying_writes: Vec<SelfModifyingWrite>,
+    invalid: Vec<usize>,
+}
+
+impl<C: Cell> Analysis<C> {
+    /// Every basic block, in address order.
+    pub fn blocks(&self) -> impl Iterator<Item = &Block<C>> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&Block<C>> {
+        self.blocks.get(&start)
+    }
+
+    /// Runs of addresses that are never reached, either as an instruction or as one's
+    /// parameters, which is normally where a program keeps its data.
+    pub fn unreachable(&self) -> &[Range<usize>] {
+        &self.unreachable
+    }
+
+    /// The addresses of jumps whose targets are read from memory.
+    pub fn indirect_jumps(&self) -> &[usize] {
+        &self.indirect_jumps
+    }
+
+    /// Writes with a fixed target that lands inside reachable code. Writes through relative
+    /// mode parameters are never included, since where they land depends on the relative base.
+    pub fn self_modifying_writes(&self) -> &[SelfModifyingWrite] {
+        &self.self_modifying_writes
+    }
+
+    /// Reachable addresses that don't hold an instruction, or hold one that runs off the end of
+    /// the program.
+    pub fn invalid(&self) -> &[usize] {
+        &self.invalid
+    }
+
+    /// Renders the control-flow graph in Graphviz DOT format.
+    ///
+    /// Jumps are labelled edges, blocks ending in indirect jumps have a dashed edge to a `?`
+    /// node, and blocks containing self-modifying writes are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks() {
+            let mut label = String::new();
+            for entry in &block.instructions {
+                write!(label, "{}: {}\\l", entry.address(), entry).unwrap();
+            }
+            let modifies_code = self
+                .self_modifying_writes
+                .iter()
+                .any(|write| (block.start..block.end).contains(&write.address));
+            let style = if modifies_code { ", color=red" } else { "" };
+            writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style).unwrap();
+        }
+        for range in &self.unreachable {
+            writeln!(
+                dot,
+                "    u{} [label=\"unreachable {}..{}\", style=dotted];",
+                range.start, range.start, range.end
+            )
+            .unwrap();
+        }
+        if !self.indirect_jumps.is_empty() {
+            dot.push_str("    indirect [label=\"?\", shape=diamond];\n");
+        }
+
+        for block in self.blocks() {
+            if let Some(target) = block.jump {
+                writeln!(dot, "    b{} -> b{} [label=\"jump\"];", block.start, target).unwrap();
+            }
+            if let Some(next) = block.fall_through {
+                writeln!(dot, "    b{} -> b{};", block.start, next).unwrap();
+            }
+            if block.indirect {
+                writeln!(dot, "    b{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Where control can go after an instruction.
+struct Flow {
+    jump: Option<usize>,
+    fall_through: Option<usize>,
+    indirect: bool,
+}
+
+fn flow<C: Cell>(address: usize, instruction: &IntCode, parameters: &[C]) -> Flow {
+    let next = address + instruction.instruction_width();
+    let (jump_if_true, condition, target) = match instruction {
+        IntCode::Halt => {
+            return Flow {
+                jump: None,
+                fall_through: None,
+                indirect: false,
+            }
+        }
+        IntCode::JumpIfTrue(condition, target) => (true, condition, target),
+        IntCode::JumpIfFalse(condition, target) => (false, condition, target),
+        _ => {
+            return Flow {
+                jump: None,
+                fall_through: Some(next),
+                indirect: false,
+            }
+        }
+    };
+
+    // A condition in immediate mode means the jump is either always or never taken.
+    let (can_jump, can_fall_through) = match condition {
+        OperationMode::Immediate => {
+            let jumps = parameters[0].is_zero() != jump_if_true;
+            (jumps, !jumps)
+        }
+        _ => (true, true),
+    };
+    let indirect = can_jump && *target != OperationMode::Immediate;
+    let jump = match target {
+        OperationMode::Immediate if can_jump => Memory::index(&parameters[1]).ok(),
+        _ => None,
+    };
+
+    Flow {
+        jump,
+        fall_through: if can_fall_through { Some(next) } else { None },
+        indirect,
+    }
+}
+
+/// Finds the basic blocks of a program, following control flow from address 0.
+pub fn analyze<C: Cell>(memory: &[C]) -> Analysis<C> {
+    analyze_from(memory, 0)
+}
+
+/// Like `analyze`, but following control flow from `start`, e.g. the instruction pointer of a
+/// machine that has already run its self-modifying setup code.
+pub fn analyze_from<C: Cell>(memory: &[C], start: usize) -> Analysis<C> {
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut invalid = BTreeSet::new();
+    let mut pending = vec![start];
+    leaders.insert(start);
+
+    while let Some(address) = pending.pop() {
+        if instructions.contains_key(&address) || invalid.contains(&address) {
+            continue;
+        }
+        let (instruction, parameters) = match decode_at(memory, address) {
+            Some(decoded) => decoded,
+            None => {
+                invalid.insert(address);
+                continue;
+            }
+        };
+
+        let flow = flow(address, &instruction, &parameters);
+        if let IntCode::JumpIfTrue(..) | IntCode::JumpIfFalse(..) = instruction {
+            leaders.extend(flow.jump);
+            leaders.extend(flow.fall_through);
+        }
+        pending.extend(flow.jump);
+        pending.extend(flow.fall_through);
+        instructions.insert(address, (instruction, parameters, flow));
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in &leaders {
+        if !instructions.contains_key(&start) {
+            continue;
+        }
+        let mut entries = Vec::new();
+        let mut address = start;
+        let (jump, fall_through, indirect) = loop {
+            let (instruction, parameters, flow) = &instructions[&address];
+            entries.push(Entry::Instruction {
+                address,
+                instruction: *instruction,
+                parameters: parameters.clone(),
+            });
+            address += instruction.instruction_width();
+
+            let ends_block = flow.jump.is_some() || flow.indirect || flow.fall_through.is_none();
+            match flow.fall_through {
+                Some(next) if !ends_block && !leaders.contains(&next) => {
+                    if instructions.contains_key(&next) {
+                        continue;
+                    }
+                    break (None, None, false);
+                }
+                _ => break (flow.jump, flow.fall_through, flow.indirect),
+            }
+        };
+        let valid = |target: Option<usize>| target.filter(|t| instructions.contains_key(t));
+        blocks.insert(
+            start,
+            Block {
+                start,
+                end: address,
+                instructions: entries,
+                jump: valid(jump),
+                fall_through: valid(fall_through),
+                indirect,
+            },
+        );
+    }
+
+    // Which instruction, if any, each address belongs to.
+    let mut owner = vec![None; memory.len()];
+    for (&address, (instruction, _, _)) in &instructions {
+        for word in &mut owner[address..address + instruction.instruction_width()] {
+            word.get_or_insert(address);
+        }
+    }
+    for &address in &invalid {
+        if let Some(word) = owner.get_mut(address) {
+            word.get_or_insert(address);
+        }
+    }
+
+    let mut unreachable: Vec<Range<usize>> = Vec::new();
+    for (address, _) in owner.iter().enumerate().filter(|(_, o)| o.is_none()) {
+        match unreachable.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => unreachable.push(address..address + 1),
+        }
+    }
+
+    let mut indirect_jumps = Vec::new();
+    let mut self_modifying_writes = Vec::new();
+    for (&address, (instruction, parameters, flow)) in &instructions {
+        if flow.indirect {
+            indirect_jumps.push(address);
+        }
+        if !instruction.writes_to_memory() {
+            continue;
+        }
+        if let (Some(OperationMode::Position), Some(target)) =
+            (instruction.modes().last(), parameters.last())
+        {
+            let target = match Memory::index(target) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            if let Some(Some(instruction)) = owner.get(target) {
+                self_modifying_writes.push(SelfModifyingWrite {
+                    address,
+                    target,
+                    instruction: *instruction,
+                });
+            }
+        }
+    }
+
+    Analysis {
+        blocks,
+        unreachable,
+        indirect_jumps,
+        self_modifying_writes,
+        invalid: invalid.into_iter().collect(),
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn it_finds_blocks_and_indirect_jumps() {
+        // The day 5 example that outputs whether its input was non-zero, using a jump.
+        let analysis = analyze(&[3_i64, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9]);
+
+        let blocks = analysis.blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!((blocks[0].start(), blocks[0].end()), (0, 5));
+        assert!(blocks[0].is_indirect());
+        assert_eq!(blocks[0].successors().collect::<Vec<_>>(), vec![5]);
+        assert_eq!((blocks[1].start(), blocks[1].end()), (5, 12));
+        assert_eq!(blocks[1].successors().count(), 0);
+
+        assert_eq!(analysis.indirect_jumps(), &[2]);
+        assert_eq!(analysis.unreachable(), &[12..16]);
+        assert!(analysis.self_modifying_writes().is_empty());
+        assert!(analysis.invalid().is_empty());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn it_follows_unconditional_jumps_and_spots_self_modification() {
+        let analysis = analyze(&[1105_i64, 1, 7, 1101, 0, 0, 0, 1101, 2, 3, 8, 99]);
+
+        let entry = analysis.block(0).unwrap();
+        assert_eq!(entry.jump(), Some(7));
+        assert_eq!(entry.fall_through(), None);
+        assert_eq!(analysis.unreachable(), &[3..7]);
+        assert_eq!(
+            analysis.self_modifying_writes(),
+            &[SelfModifyingWrite {
+                address: 7,
+                target: 8,
+                instruction: 7,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_exports_dot() {
+        let analysis = analyze(&[1105_i64, 1, 7, 1101, 0, 0, 0, 1101, 2, 3, 8, 99]);
+
+        assert_eq!(
+            analysis.to_dot(),
+            "digraph intcode {\n    \
+                 node [shape=box, fontname=\"monospace\"];\n    \
+                 b0 [label=\"0: JT #1, #7\\l\"];\n    \
+                 b7 [label=\"7: ADD #2, #3 -> [8]\\l11: HLT\\l\", color=red];\n    \
+                 u3 [label=\"unreachable 3..7\", style=dotted];\n    \
+                 b0 -> b7 [label=\"jump\"];\n\
+             }\n"
+        );
+    }
+}
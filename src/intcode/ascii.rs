@@ -0,0 +1,133 @@
+//! An adaptor for programs that talk in ASCII text, one character per value.
+
+use super::{Cell, ErrorKind, ExecutionError, Machine, Status, StepResult};
+
+/// Wraps a `Machine`, encoding lines of input as characters and decoding its output back into
+/// text.
+///
+/// Output values that aren't ASCII characters, such as a final score or a puzzle answer, are
+/// kept apart from the text rather than being mangled into it.
+#[derive(Debug, Clone)]
+pub struct AsciiMachine<C: Cell = i64> {
+    machine: Machine<C>,
+    text: String,
+    values: Vec<C>,
+}
+
+impl<C: Cell> AsciiMachine<C> {
+    pub fn new(machine: Machine<C>) -> Self {
+        AsciiMachine {
+            machine,
+            text: String::new(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<C> {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> Machine<C> {
+        self.machine
+    }
+
+    /// Queues `line` followed by a newline. Every character is sent as its code point, so
+    /// anything outside ASCII is up to the program to make sense of.
+    pub fn push_line(&mut self, line: &str) {
+        self.machine.extend_input(
+            line.chars()
+                .chain(Some('\n'))
+                .map(|c| C::from_i64(c as i64)),
+        );
+    }
+
+    /// Runs the program until it halts or wants input that hasn't been queued, returning which
+    /// of the two it was.
+    pub fn run(&mut self) -> Result<Status, ExecutionError> {
+        loop {
+            match self.machine.run()? {
+                StepResult::Output(value) => match value.to_i64() {
+                    Some(code @ 0..=127) => self.text.push(code as u8 as char),
+                    _ => self.values.push(value),
+                },
+                StepResult::NeedsInput => return Ok(Status::AwaitingInput),
+                StepResult::Halted => return Ok(Status::Halted),
+            }
+        }
+    }
+
+    /// Hands over the text output so far, leaving nothing behind.
+    pub fn take_text(&mut self) -> String {
+        std::mem::take(&mut self.text)
+    }
+
+    /// Hands over the non-ASCII values output so far, leaving nothing behind.
+    pub fn take_values(&mut self) -> Vec<C> {
+        std::mem::take(&mut self.values)
+    }
+}
+
+/// Runs a program to completion with `input` as its text input, returning its text output and
+/// any non-ASCII values it output along the way.
+pub fn execute_ascii<C: Cell>(
+    program: Vec<C>,
+    input: &[&str],
+) -> Result<(String, Vec<C>), ExecutionError> {
+    let mut machine = AsciiMachine::new(Machine::new(program));
+    for line in input {
+        machine.push_line(line);
+    }
+    match machine.run()? {
+        Status::Halted => Ok((machine.take_text(), machine.take_values())),
+        _ => {
+            let machine = &machine.machine;
+            Err(machine.error(machine.ip, ErrorKind::MissingInput.into()))
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::assembler::assemble;
+
+    #[allow(dead_code)]
+    fn echo() -> Vec<i64> {
+        assemble(
+            "
+            loop:   IN -> [char]
+                    OUT [char]
+                    EQ [char], #10 -> [done]
+                    JF [done], #loop
+                    OUT #1000
+                    HLT
+            char:   .data 0
+            done:   .data 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_encodes_lines_and_separates_non_ascii_output() {
+        let mut machine = AsciiMachine::new(Machine::new(echo()));
+
+        machine.push_line("hel");
+        assert_eq!(machine.run().unwrap(), Status::Halted);
+        assert_eq!(machine.take_text(), "hel\n");
+        assert_eq!(machine.take_values(), vec![1000]);
+    }
+
+    #[test]
+    fn it_pauses_for_more_input() {
+        let mut machine = AsciiMachine::new(Machine::new(echo()));
+
+        assert_eq!(machine.run().unwrap(), Status::AwaitingInput);
+        assert_eq!(machine.take_text(), "");
+        assert_eq!(
+            execute_ascii(echo(), &["x"]).unwrap(),
+            ("x\n".to_owned(), vec![1000])
+        );
+    }
+}
@@ -0,0 +1,725 @@
+//! A small assembler for writing Intcode programs by hand.
+//!
+//! Each line holds an optional `label:`, then an instruction or directive, then an optional
+//! `; comment`. Instructions use the disassembler's mnemonics (`ADD`, `MUL`, `IN`, `OUT`, `JT`,
+//! `JF`, `LT`, `EQ`, `ARB`, `HLT`) or the names of the `IntCode` variants, case-insensitively.
+//! Every operand spells out its mode:
+//!
+//! * `#5` is immediate,
+//! * `[12]` is position,
+//! * `[rb+3]` (or `[rb-3]`, or `[rb]`) is relative.
+//!
+//! An instruction's destination may be separated from its other operands with `->`, so
+//! `ADD [12], #5 -> [3]` is just as valid as `ADD [12], #5, [3]`.
+//!
+//! Anywhere a number can appear, so can a label or constant, optionally offset by further
+//! terms, as in `[counter+1]`. Two directives are supported:
+//!
+//! * `.data 1, 2, three` emits its values as raw words,
+//! * `.const NAME = 10` defines a constant.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{Cell, DecodeError, IntCode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AssembleError {
+    fn new(line: usize, column: usize, message: String) -> Self {
+        AssembleError {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(i64),
+    Hash,
+    Arrow,
+    Colon,
+    Comma,
+    Equals,
+    Plus,
+    Minus,
+    OpenBracket,
+    CloseBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "`{}`", name),
+            Token::Number(value) => write!(f, "`{}`", value),
+            Token::Hash => write!(f, "`#`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Equals => write!(f, "`=`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::OpenBracket => write!(f, "`[`"),
+            Token::CloseBracket => write!(f, "`]`"),
+        }
+    }
+}
+
+/// Splits a line into tokens, each paired with its 1-based column.
+fn tokenize(line: usize, text: &str) -> Result<Vec<(usize, Token)>, AssembleError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let token = match c {
+            '#' => Token::Hash,
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            '+' => Token::Plus,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            '-' if chars.get(i + 1) == Some(&'>') => {
+                i += 1;
+                Token::Arrow
+            }
+            '-' => Token::Minus,
+            c if c.is_ascii_digit() => {
+                let start = i;
+                while i + 1 < chars.len() && chars[i + 1].is_ascii_digit() {
+                    i += 1;
+                }
+                let digits = chars[start..=i].iter().collect::<String>();
+                Token::Number(digits.parse().map_err(|_| {
+                    AssembleError::new(line, column, format!("Number `{}` is too large", digits))
+                })?)
+            }
+            c if c.is_alphabetic() || c == '_' || c == '.' => {
+                let start = i;
+                while i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || chars[i + 1] == '_')
+                {
+                    i += 1;
+                }
+                Token::Identifier(chars[start..=i].iter().collect())
+            }
+            c => {
+                return Err(AssembleError::new(
+                    line,
+                    column,
+                    format!("Unexpected character `{}`", c),
+                ))
+            }
+        };
+
+        tokens.push((column, token));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// A sum of numbers and symbols, resolved once every label's address is known.
+#[derive(Debug, Clone)]
+struct Expression {
+    line: usize,
+    column: usize,
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Immediate(Expression),
+    Position(Expression),
+    Relative(Expression),
+}
+
+impl Operand {
+    fn mode_digit(&self) -> i32 {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+
+    fn expression(&self) -> &Expression {
+        match self {
+            Operand::Immediate(e) | Operand::Position(e) | Operand::Relative(e) => e,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Statement {
+    Instruction(IntCode, Vec<Operand>),
+    Data(Vec<Expression>),
+}
+
+/// The opcode and parameter count for a mnemonic or `IntCode` variant name.
+fn lookup_mnemonic(name: &str) -> Option<(i32, usize)> {
+    match name.to_ascii_uppercase().as_str() {
+        "ADD" => Some((1, 3)),
+        "MUL" | "MULTIPLY" => Some((2, 3)),
+        "IN" | "STOREINPUT" => Some((3, 1)),
+        "OUT" | "LOADOUTPUT" => Some((4, 1)),
+        "JT" | "JUMPIFTRUE" => Some((5, 2)),
+        "JF" | "JUMPIFFALSE" => Some((6, 2)),
+        "LT" | "LESSTHAN" => Some((7, 3)),
+        "EQ" | "EQUALS" => Some((8, 3)),
+        "ARB" | "ADJUSTRELATIVEBASE" => Some((9, 1)),
+        "HLT" | "HALT" => Some((99, 0)),
+        _ => None,
+    }
+}
+
+/// A cursor over the tokens of a single line.
+struct Parser {
+    line: usize,
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end_column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map(|(column, _)| *column)
+            .unwrap_or(self.end_column)
+    }
+
+    fn error(&self, message: String) -> AssembleError {
+        AssembleError::new(self.line, self.column(), message)
+    }
+
+    fn unexpected(&self, expected: &str) -> AssembleError {
+        match self.peek() {
+            Some(token) => self.error(format!("Expected {}, found {}", expected, token)),
+            None => self.error(format!("Expected {}, found end of line", expected)),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, expected: &Token) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), AssembleError> {
+        if self.eat(&expected) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&expected.to_string()))
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn term(&mut self) -> Result<Term, AssembleError> {
+        match self.peek() {
+            Some(Token::Number(value)) => {
+                let value = *value;
+                self.position += 1;
+                Ok(Term::Number(value))
+            }
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(Term::Symbol(name))
+            }
+            _ => Err(self.unexpected("a number or symbol")),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, AssembleError> {
+        let column = self.column();
+        let negative = self.eat(&Token::Minus);
+        let mut terms = vec![(negative, self.term()?)];
+
+        loop {
+            if self.eat(&Token::Plus) {
+                terms.push((false, self.term()?));
+            } else if self.eat(&Token::Minus) {
+                terms.push((true, self.term()?));
+            } else {
+                break;
+            }
+        }
+
+        Ok(Expression {
+            line: self.line,
+            column,
+            terms,
+        })
+    }
+
+    fn operand(&mut self) -> Result<Operand, AssembleError> {
+        if self.eat(&Token::Hash) {
+            return Ok(Operand::Immediate(self.expression()?));
+        }
+
+        if !self.eat(&Token::OpenBracket) {
+            return Err(self.unexpected("an operand"));
+        }
+
+        let operand = match self.peek() {
+            Some(Token::Identifier(name)) if name == "rb" => {
+                let column = self.column();
+                self.position += 1;
+                if self.peek() == Some(&Token::CloseBracket) {
+                    Operand::Relative(Expression {
+                        line: self.line,
+                        column,
+                        terms: vec![(false, Term::Number(0))],
+                    })
+                } else {
+                    let negative = match self.next() {
+                        Some(Token::Plus) => false,
+                        Some(Token::Minus) => true,
+                        _ => {
+                            self.position -= 1;
+                            return Err(self.unexpected("`+`, `-` or `]`"));
+                        }
+                    };
+                    let mut offset = self.expression()?;
+                    if negative {
+                        offset.terms[0].0 = !offset.terms[0].0;
+                    }
+                    Operand::Relative(offset)
+                }
+            }
+            _ => Operand::Position(self.expression()?),
+        };
+
+        self.expect(Token::CloseBracket)?;
+        Ok(operand)
+    }
+
+    fn instruction(&mut self, column: usize, name: &str) -> Result<Statement, AssembleError> {
+        let (opcode, count) = lookup_mnemonic(name).ok_or_else(|| {
+            AssembleError::new(self.line, column, format!("Unknown mnemonic `{}`", name))
+        })?;
+
+        let mut operands = Vec::new();
+        let mut columns = Vec::new();
+        while !self.at_end() {
+            if operands.is_empty() {
+                // `IN -> [9]` has nothing to separate its destination from.
+                self.eat(&Token::Arrow);
+            } else if !self.eat(&Token::Comma) && !self.eat(&Token::Arrow) {
+                return Err(self.unexpected("`,` or `->`"));
+            }
+            columns.push(self.column());
+            operands.push(self.operand()?);
+        }
+
+        if operands.len() != count {
+            return Err(AssembleError::new(
+                self.line,
+                column,
+                format!(
+                    "`{}` takes {} operands, but {} were given",
+                    name,
+                    count,
+                    operands.len()
+                ),
+            ));
+        }
+
+        let word = operands
+            .iter()
+            .rev()
+            .fold(0, |digits, operand| digits * 10 + operand.mode_digit())
+            * 100
+            + opcode;
+
+        let instruction = IntCode::try_from(word).map_err(|err| match err {
+            DecodeError::ImmediateWrite { parameter, .. } => AssembleError::new(
+                self.line,
+                columns[parameter - 1],
+                format!("`{}` cannot write to an immediate operand", name),
+            ),
+            err => AssembleError::new(self.line, column, err.to_string()),
+        })?;
+
+        Ok(Statement::Instruction(instruction, operands))
+    }
+}
+
+#[derive(Default)]
+struct Symbols {
+    values: HashMap<String, Expression>,
+}
+
+impl Symbols {
+    fn define(
+        &mut self,
+        line: usize,
+        column: usize,
+        name: &str,
+        value: Expression,
+    ) -> Result<(), AssembleError> {
+        if name == "rb" || lookup_mnemonic(name).is_some() {
+            return Err(AssembleError::new(
+                line,
+                column,
+                format!("`{}` is reserved", name),
+            ));
+        }
+        if self.values.insert(name.to_owned(), value).is_some() {
+            return Err(AssembleError::new(
+                line,
+                column,
+                format!("`{}` is already defined", name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, expression: &Expression, depth: usize) -> Result<i64, AssembleError> {
+        let error = |message| AssembleError::new(expression.line, expression.column, message);
+
+        if depth > self.values.len() {
+            return Err(error("Constant is defined in terms of itself".to_owned()));
+        }
+
+        expression
+            .terms
+            .iter()
+            .try_fold(0_i64, |total, (negative, term)| {
+                let value = match term {
+                    Term::Number(value) => *value,
+                    Term::Symbol(name) => match self.values.get(name) {
+                        Some(definition) => self.resolve(definition, depth + 1)?,
+                        None => return Err(error(format!("Unknown symbol `{}`", name))),
+                    },
+                };
+                let result = if *negative {
+                    total.checked_sub(value)
+                } else {
+                    total.checked_add(value)
+                };
+                result.ok_or_else(|| error("Expression overflows".to_owned()))
+            })
+    }
+
+    /// Resolves an expression into a memory cell, failing if the value doesn't fit in one.
+    fn cell<C: Cell>(&self, expression: &Expression) -> Result<C, AssembleError> {
+        let value = self.resolve(expression, 0)?;
+        C::try_from_i64(value).ok_or_else(|| {
+            AssembleError::new(
+                expression.line,
+                expression.column,
+                format!("Value `{}` does not fit in a cell", value),
+            )
+        })
+    }
+}
+
+/// Assembles source code into a program that `execute` can run.
+pub fn assemble<C: Cell>(source: &str) -> Result<Vec<C>, AssembleError> {
+    let mut symbols = Symbols::default();
+    let mut statements = Vec::new();
+    let mut address = 0_i64;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut parser = Parser {
+            line,
+            tokens: tokenize(line, text)?,
+            position: 0,
+            end_column: text.chars().count() + 1,
+        };
+
+        while let (Some((column, Token::Identifier(name))), Some((_, Token::Colon))) = (
+            parser.tokens.get(parser.position),
+            parser.tokens.get(parser.position + 1),
+        ) {
+            let (column, name) = (*column, name.clone());
+            let here = Expression {
+                line,
+                column,
+                terms: vec![(false, Term::Number(address))],
+            };
+            symbols.define(line, column, &name, here)?;
+            parser.position += 2;
+        }
+
+        if parser.at_end() {
+            continue;
+        }
+
+        let column = parser.column();
+        let statement = match parser.next() {
+            Some(Token::Identifier(directive)) if directive == ".const" => {
+                let name_column = parser.column();
+                let name = match parser.next() {
+                    Some(Token::Identifier(name)) => name,
+                    _ => {
+                        parser.position -= 1;
+                        return Err(parser.unexpected("a constant name"));
+                    }
+                };
+                parser.expect(Token::Equals)?;
+                let value = parser.expression()?;
+                symbols.define(line, name_column, &name, value)?;
+                None
+            }
+            Some(Token::Identifier(directive)) if directive == ".data" => {
+                let mut values = vec![parser.expression()?];
+                while parser.eat(&Token::Comma) {
+                    values.push(parser.expression()?);
+                }
+                Some(Statement::Data(values))
+            }
+            Some(Token::Identifier(directive)) if directive.starts_with('.') => {
+                return Err(AssembleError::new(
+                    line,
+                    column,
+                    format!("Unknown directive `{}`", directive),
+                ))
+            }
+            Some(Token::Identifier(name)) => Some(parser.instruction(column, &name)?),
+            _ => {
+                parser.position -= 1;
+                return Err(parser.unexpected("an instruction or directive"));
+            }
+        };
+
+        if !parser.at_end() {
+            return Err(parser.unexpected("end of line"));
+        }
+
+        if let Some(statement) = statement {
+            address += match &statement {
+                Statement::Instruction(instruction, _) => instruction.instruction_width() as i64,
+                Statement::Data(values) => values.len() as i64,
+            };
+            statements.push(statement);
+        }
+    }
+
+    let mut program = Vec::new();
+    for statement in statements {
+        match statement {
+            Statement::Instruction(instruction, operands) => {
+                program.push(C::from_i64(i64::from(instruction.encode())));
+                for operand in operands {
+                    program.push(symbols.cell(operand.expression())?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(symbols.cell(&value)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::{disassembler::disassemble, execute};
+
+    #[test]
+    fn it_assembles_every_operand_mode() {
+        let program = assemble::<i64>(
+            "
+            ADD [12], #5 -> [3]   ; disassembler syntax
+            lt #-4, #7, [rb]
+            Equals [rb-2], [rb+3] -> [rb + 1]
+            HLT
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program,
+            vec![1001, 12, 5, 3, 21107, -4, 7, 0, 22208, -2, 3, 1, 99]
+        );
+    }
+
+    #[test]
+    fn it_resolves_labels_and_constants() {
+        let program = assemble::<i64>(
+            "
+            .const LIMIT = 3
+
+            loop:   ADD [counter], #1 -> [counter]
+                    OUT [counter]
+                    LT [counter], #LIMIT -> [flag]
+                    JT [flag], #loop
+                    HLT
+            counter: .data 0
+            flag:    .data 0
+            ",
+        )
+        .unwrap();
+
+        let mut memory = program.clone();
+        assert_eq!(execute(&mut memory, &[]).unwrap(), vec![1, 2, 3]);
+        assert_eq!(program[1], 14);
+        assert_eq!(program[12], 0);
+    }
+
+    #[test]
+    fn it_assembles_the_day_5_comparison_example() {
+        let program = assemble::<i64>(
+            "
+                IN -> [value]
+                EQ [value], [eight] -> [value]
+                OUT [value]
+                HLT
+            value: .data -1
+            eight: .data 8
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(program, vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+    }
+
+    #[test]
+    fn it_reassembles_disassembler_output() {
+        let program = vec![
+            109_i64, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 99,
+        ];
+        let source = disassemble(&program)
+            .entries()
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(assemble::<i64>(&source).unwrap(), program);
+    }
+
+    #[test]
+    fn it_reports_line_and_column_of_errors() {
+        assert_eq!(
+            assemble::<i64>("HLT\n  ADD #1, #2 -> #3"),
+            Err(AssembleError::new(
+                2,
+                17,
+                "`ADD` cannot write to an immediate operand".to_owned()
+            ))
+        );
+        assert_eq!(
+            assemble::<i64>("  JT #1, [nowhere]"),
+            Err(AssembleError::new(
+                1,
+                11,
+                "Unknown symbol `nowhere`".to_owned()
+            ))
+        );
+        assert_eq!(
+            assemble::<i64>("\n\n  FROB [1]"),
+            Err(AssembleError::new(
+                3,
+                3,
+                "Unknown mnemonic `FROB`".to_owned()
+            ))
+        );
+        assert_eq!(
+            assemble::<i64>("OUT [1] [2]"),
+            Err(AssembleError::new(
+                1,
+                9,
+                "Expected `,` or `->`, found `[`".to_owned()
+            ))
+        );
+        assert_eq!(
+            assemble::<i64>("OUT #1, #2"),
+            Err(AssembleError::new(
+                1,
+                1,
+                "`OUT` takes 1 operands, but 2 were given".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn it_reports_values_too_large_for_the_cell_type() {
+        assert_eq!(
+            assemble::<i32>("OUT #big\nHLT\nbig: .data 3000000000"),
+            Err(AssembleError::new(
+                3,
+                12,
+                "Value `3000000000` does not fit in a cell".to_owned()
+            ))
+        );
+        assert_eq!(
+            assemble::<i64>("big: .data 3000000000").unwrap()[0],
+            3_000_000_000
+        );
+    }
+
+    #[test]
+    fn it_counts_columns_in_characters() {
+        assert_eq!(
+            assemble::<i64>("OUT [ ; héllo"),
+            Err(AssembleError::new(
+                1,
+                14,
+                "Expected a number or symbol, found end of line".to_owned()
+            ))
+        );
+    }
+}
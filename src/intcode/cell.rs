@@ -0,0 +1,101 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// A value that can be stored in a single Intcode memory cell.
+///
+/// Arithmetic is checked so that programs working with numbers too large for the cell type
+/// fail loudly instead of silently wrapping.
+pub trait Cell: Clone + Debug + Display + FromStr + Hash + PartialEq + PartialOrd {
+    fn from_i64(value: i64) -> Self;
+
+    /// Like `from_i64`, but returns `None` for values the cell type can't hold.
+    fn try_from_i64(value: i64) -> Option<Self>;
+
+    /// The cell's value as an `i64`, if it fits in one.
+    fn to_i64(&self) -> Option<i64>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn zero() -> Self {
+        Self::from_i64(0)
+    }
+
+    fn one() -> Self {
+        Self::from_i64(1)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+}
+
+impl Cell for i32 {
+    fn from_i64(value: i64) -> Self {
+        i32::try_from(value).expect("Value does not fit in an i32 cell")
+    }
+
+    fn try_from_i64(value: i64) -> Option<Self> {
+        i32::try_from(value).ok()
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(i64::from(*self))
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i32::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i32::checked_mul(*self, *other)
+    }
+}
+
+impl Cell for i64 {
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    fn try_from_i64(value: i64) -> Option<Self> {
+        Some(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *other)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl Cell for num_bigint::BigInt {
+    fn from_i64(value: i64) -> Self {
+        num_bigint::BigInt::from(value)
+    }
+
+    fn try_from_i64(value: i64) -> Option<Self> {
+        Some(num_bigint::BigInt::from(value))
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        num_traits::ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
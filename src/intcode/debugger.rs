@@ -0,0 +1,225 @@
+use std::collections::BTreeSet;
+
+use super::disassembler::{decode_at, Entry};
+use super::{Cell, ExecutionError, Machine, StepResult};
+
+/// The widest instruction, opcode included, so we know how much memory to look at when decoding.
+const MAX_INSTRUCTION_WIDTH: usize = 4;
+
+/// Why a `Debugger` handed control back to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason<C: Cell = i64> {
+    /// A single instruction was executed and nothing else of note happened.
+    Stepped,
+    /// Execution reached an address with a breakpoint on it. The instruction there hasn't run.
+    Breakpoint(usize),
+    /// The last instruction changed the value of a watched memory cell.
+    Watchpoint {
+        address: usize,
+        old: C,
+        new: C,
+    },
+    NeedsInput,
+    Halted,
+}
+
+/// Wraps a `Machine`, letting it be stepped through one instruction at a time or run until it
+/// hits a breakpoint or modifies a watched memory cell.
+///
+/// Anything the program outputs is collected rather than interrupting execution, see `output`.
+#[derive(Debug, Clone)]
+pub struct Debugger<C: Cell = i64> {
+    machine: Machine<C>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    output: Vec<C>,
+}
+
+impl<C: Cell> Debugger<C> {
+    pub fn new(machine: Machine<C>) -> Self {
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn machine(&self) -> &Machine<C> {
+        &self.machine
+    }
+
+    /// Gives direct access to the machine, to inspect or modify its memory and registers.
+    pub fn machine_mut(&mut self) -> &mut Machine<C> {
+        &mut self.machine
+    }
+
+    pub fn into_machine(self) -> Machine<C> {
+        self.machine
+    }
+
+    /// Returns false if there already was a breakpoint at `address`.
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Returns false if there was no breakpoint at `address`.
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    /// Returns false if `address` was already being watched.
+    pub fn add_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.insert(address)
+    }
+
+    /// Returns false if `address` wasn't being watched.
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watchpoints.iter().cloned()
+    }
+
+    /// Everything the program has output so far.
+    pub fn output(&self) -> &[C] {
+        &self.output
+    }
+
+    /// Hands over the output collected so far, leaving nothing behind.
+    pub fn take_output(&mut self) -> Vec<C> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// The instruction at the instruction pointer, or the raw word there if it doesn't decode.
+    pub fn current_instruction(&self) -> Entry<C> {
+        let address = self.machine.ip();
+        let memory = self.machine.memory();
+        let window = (address..address + MAX_INSTRUCTION_WIDTH)
+            .map(|index| memory.get(index))
+            .collect::<Vec<_>>();
+
+        match decode_at(&window, 0) {
+            Some((instruction, parameters)) => Entry::Instruction {
+                address,
+                instruction,
+                parameters,
+            },
+            None => Entry::Data {
+                address,
+                value: window[0].clone(),
+            },
+        }
+    }
+
+    /// Executes a single instruction, ignoring breakpoints.
+    pub fn step(&mut self) -> Result<StopReason<C>, ExecutionError> {
+        let memory = self.machine.memory();
+        let watched = self
+            .watchpoints
+            .iter()
+            .map(|&address| (address, memory.get(address)))
+            .collect::<Vec<_>>();
+
+        match self.machine.step()? {
+            Some(StepResult::Output(value)) => self.output.push(value),
+            Some(StepResult::NeedsInput) => return Ok(StopReason::NeedsInput),
+            Some(StepResult::Halted) => return Ok(StopReason::Halted),
+            None => {}
+        }
+
+        let memory = self.machine.memory();
+        for (address, old) in watched {
+            let new = memory.get(address);
+            if new != old {
+                return Ok(StopReason::Watchpoint { address, old, new });
+            }
+        }
+        Ok(StopReason::Stepped)
+    }
+
+    /// Executes instructions until the machine hits a breakpoint or watchpoint, halts or needs
+    /// input. A breakpoint at the instruction pointer when this is called doesn't count, so it
+    /// can be used to carry on after stopping at one.
+    pub fn resume(&mut self) -> Result<StopReason<C>, ExecutionError> {
+        loop {
+            match self.step()? {
+                StopReason::Stepped => {
+                    let ip = self.machine.ip();
+                    if self.breakpoints.contains(&ip) {
+                        return Ok(StopReason::Breakpoint(ip));
+                    }
+                }
+                reason => return Ok(reason),
+            }
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn it_stops_at_breakpoints_and_carries_on_past_them() {
+        // Counts down from 3, outputting each value.
+        let program = vec![4, 12, 1001, 12, -1, 12, 1005, 12, 0, 99, 0, 0, 3];
+        let mut debugger = Debugger::new(Machine::new(program));
+        debugger.add_breakpoint(6);
+
+        assert_eq!(debugger.resume().unwrap(), StopReason::Breakpoint(6));
+        assert_eq!(debugger.current_instruction().to_string(), "JT [12], #0");
+        assert_eq!(debugger.resume().unwrap(), StopReason::Breakpoint(6));
+        assert_eq!(debugger.output(), &[3, 2]);
+
+        debugger.remove_breakpoint(6);
+        assert_eq!(debugger.resume().unwrap(), StopReason::Halted);
+        assert_eq!(debugger.take_output(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn it_stops_when_a_watched_cell_changes() {
+        let program = vec![1101, 1, 1, 9, 1101, 0, 2, 9, 99, 2];
+        let mut debugger = Debugger::new(Machine::new(program));
+        debugger.add_watchpoint(9);
+
+        assert_eq!(debugger.resume().unwrap(), StopReason::Halted);
+
+        let program = vec![1101, 1, 1, 9, 1101, 0, 3, 9, 99, 2];
+        let mut debugger = Debugger::new(Machine::new(program));
+        debugger.add_watchpoint(9);
+
+        assert_eq!(
+            debugger.resume().unwrap(),
+            StopReason::Watchpoint {
+                address: 9,
+                old: 2,
+                new: 3
+            }
+        );
+        assert_eq!(debugger.machine().ip(), 8);
+    }
+
+    #[test]
+    fn memory_and_registers_can_be_modified_between_steps() {
+        let mut debugger = Debugger::new(Machine::new(vec![3, 6, 204, 1, 99, 0, 0]));
+
+        assert_eq!(debugger.step().unwrap(), StopReason::NeedsInput);
+        debugger.machine_mut().push_input(7);
+        assert_eq!(debugger.step().unwrap(), StopReason::Stepped);
+
+        debugger.machine_mut().memory_mut().set(5, 42);
+        debugger.machine_mut().set_relative_base(4);
+        assert_eq!(debugger.current_instruction().to_string(), "OUT [rb+1]");
+        assert_eq!(debugger.step().unwrap(), StopReason::Stepped);
+        assert_eq!(debugger.output(), &[42]);
+
+        debugger.machine_mut().set_ip(0);
+        assert_eq!(debugger.step().unwrap(), StopReason::NeedsInput);
+    }
+}
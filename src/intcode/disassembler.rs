@@ -0,0 +1,221 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use super::{Cell, IntCode, OperationMode};
+
+/// A single line of a disassembled program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<C: Cell = i64> {
+    Instruction {
+        address: usize,
+        instruction: IntCode,
+        parameters: Vec<C>,
+    },
+    /// A word that doesn't decode to an instruction, or whose parameters would run off the end
+    /// of memory.
+    Data { address: usize, value: C },
+}
+
+impl<C: Cell> Entry<C> {
+    pub fn address(&self) -> usize {
+        match self {
+            Entry::Instruction { address, .. } | Entry::Data { address, .. } => *address,
+        }
+    }
+
+    /// How many words of memory the entry covers.
+    pub fn width(&self) -> usize {
+        match self {
+            Entry::Instruction { instruction, .. } => instruction.instruction_width(),
+            Entry::Data { .. } => 1,
+        }
+    }
+}
+
+impl<C: Cell> fmt::Display for Entry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::Instruction {
+                instruction,
+                parameters,
+                ..
+            } => write!(f, "{}", format_instruction(instruction, parameters)),
+            Entry::Data { value, .. } => write!(f, "DATA {}", value),
+        }
+    }
+}
+
+/// Formats an operand the way the disassembler does: `[12]` for position mode, `#5` for
+/// immediate mode and `[rb+3]` for relative mode.
+pub fn format_operand<C: Cell>(mode: OperationMode, value: &C) -> String {
+    match mode {
+        OperationMode::Position => format!("[{}]", value),
+        OperationMode::Immediate => format!("#{}", value),
+        OperationMode::Relative => {
+            let offset = value.to_string();
+            if offset.starts_with('-') {
+                format!("[rb{}]", offset)
+            } else {
+                format!("[rb+{}]", offset)
+            }
+        }
+    }
+}
+
+/// Formats an instruction and the parameters following it in memory, e.g.
+/// `ADD [12], #5 -> [3]`.
+pub fn format_instruction<C: Cell>(instruction: &IntCode, parameters: &[C]) -> String {
+    let mut operands = instruction
+        .modes()
+        .into_iter()
+        .zip(parameters)
+        .map(|(mode, value)| format_operand(mode, value))
+        .collect::<Vec<_>>();
+
+    let destination = if instruction.writes_to_memory() {
+        operands.pop()
+    } else {
+        None
+    };
+
+    let mut line = instruction.mnemonic().to_owned();
+    if !operands.is_empty() {
+        line.push(' ');
+        line.push_str(&operands.join(", "));
+    }
+    if let Some(destination) = destination {
+        line.push_str(" -> ");
+        line.push_str(&destination);
+    }
+    line
+}
+
+/// Decodes the instruction at `address`, if there is one that fits in `memory`.
+pub fn decode_at<C: Cell>(memory: &[C], address: usize) -> Option<(IntCode, Vec<C>)> {
+    let word = i32::try_from(memory.get(address)?.to_i64()?).ok()?;
+    let instruction = IntCode::try_from(word).ok()?;
+    let parameters = memory.get(address + 1..address + instruction.instruction_width())?;
+    Some((instruction, parameters.to_vec()))
+}
+
+/// A disassembled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing<C: Cell = i64> {
+    entries: Vec<Entry<C>>,
+    memory: Vec<C>,
+}
+
+impl<C: Cell> Listing<C> {
+    pub fn entries(&self) -> &[Entry<C>] {
+        &self.entries
+    }
+
+    /// The entry covering `address`, if any.
+    pub fn entry_at(&self, address: usize) -> Option<&Entry<C>> {
+        self.entries
+            .iter()
+            .find(|entry| (entry.address()..entry.address() + entry.width()).contains(&address))
+    }
+}
+
+impl<C: Cell> fmt::Display for Listing<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            let address = entry.address();
+            let words = self.memory[address..address + entry.width()]
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(f, "{:>6}  {:<24}  {}", address, words, entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassembles a program, or a snapshot of a machine's memory, from address 0.
+///
+/// Memory is swept linearly: anything that decodes is treated as an instruction and the sweep
+/// continues after its parameters, and anything else is marked as data.
+pub fn disassemble<C: Cell>(memory: &[C]) -> Listing<C> {
+    let mut entries = Vec::new();
+    let mut address = 0;
+
+    while address < memory.len() {
+        let entry = match decode_at(memory, address) {
+            Some((instruction, parameters)) => Entry::Instruction {
+                address,
+                instruction,
+                parameters,
+            },
+            None => Entry::Data {
+                address,
+                value: memory[address].clone(),
+            },
+        };
+        address += entry.width();
+        entries.push(entry);
+    }
+
+    Listing {
+        entries,
+        memory: memory.to_vec(),
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::{Machine, StepResult};
+
+    #[test]
+    fn it_formats_every_operand_mode() {
+        let listing = disassemble(&[1001_i64, 12, 5, 3, 21107, -4, 7, 0, 99]);
+
+        assert_eq!(
+            listing
+                .entries()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec!["ADD [12], #5 -> [3]", "LT #-4, #7 -> [rb+0]", "HLT"]
+        );
+    }
+
+    #[test]
+    fn it_marks_undecodable_words_as_data() {
+        let listing = disassemble(&[3_i64, 9, 4, 9, 99, -1, 8, 1002]);
+
+        assert_eq!(
+            listing.to_string(),
+            "     0  3,9                       IN -> [9]\n     \
+                  2  4,9                       OUT [9]\n     \
+                  4  99                        HLT\n     \
+                  5  -1                        DATA -1\n     \
+                  6  8                         DATA 8\n     \
+                  7  1002                      DATA 1002\n"
+        );
+    }
+
+    #[test]
+    fn it_disassembles_self_modified_memory() {
+        let mut machine = Machine::new(vec![1101, 3, 4, 6, 1105, 1, 0, 99]);
+
+        assert_eq!(machine.run().unwrap(), StepResult::Halted);
+
+        let listing = disassemble(&machine.memory().to_vec().unwrap());
+        assert_eq!(listing.entries()[1].to_string(), "JT #1, #7");
+        assert_eq!(
+            listing.entry_at(6),
+            Some(&Entry::Instruction {
+                address: 4,
+                instruction: IntCode::JumpIfTrue(
+                    OperationMode::Immediate,
+                    OperationMode::Immediate
+                ),
+                parameters: vec![1, 7],
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::io;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+use super::{DecodeError, IntCode};
+
+/// What went wrong while executing an Intcode program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    Conversion,
+    NegativeAddress,
+    MissingInput,
+    MissingOutput,
+    Overflow,
+    Decode(DecodeError),
+    StepLimitExceeded {
+        limit: u64,
+    },
+    /// The machine got back into a state it had already been in, `period` instructions ago.
+    InfiniteLoop {
+        period: u64,
+    },
+    /// Memory was written at `address`, too far out to be flattened into a vector.
+    MemoryTooLarge {
+        address: usize,
+    },
+}
+
+impl From<std::num::TryFromIntError> for ErrorKind {
+    fn from(_err: std::num::TryFromIntError) -> ErrorKind {
+        ErrorKind::Conversion
+    }
+}
+
+impl From<DecodeError> for ErrorKind {
+    fn from(err: DecodeError) -> ErrorKind {
+        ErrorKind::Decode(err)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Conversion => write!(f, "Value cannot be used as an address"),
+            ErrorKind::NegativeAddress => write!(f, "Access to a negative address"),
+            ErrorKind::MissingInput => write!(f, "Program needs more input than was given"),
+            ErrorKind::MissingOutput => write!(f, "Program halted without producing any output"),
+            ErrorKind::Overflow => write!(f, "Arithmetic overflow"),
+            ErrorKind::Decode(err) => write!(f, "{}", err),
+            ErrorKind::StepLimitExceeded { limit } => {
+                write!(f, "Program didn't halt within {} steps", limit)
+            }
+            ErrorKind::InfiniteLoop { period } => write!(
+                f,
+                "Program is stuck in a loop, repeating every {} steps",
+                period
+            ),
+            ErrorKind::MemoryTooLarge { address } => write!(
+                f,
+                "Memory was written at address {}, too far out to return as a vector",
+                address
+            ),
+        }
+    }
+}
+
+/// An error raised by a `Machine`, along with where in the program it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionError {
+    kind: ErrorKind,
+    ip: usize,
+    word: Option<i64>,
+    parameter: Option<usize>,
+    recent: Vec<(usize, IntCode)>,
+}
+
+impl ExecutionError {
+    pub(super) fn new(
+        kind: ErrorKind,
+        ip: usize,
+        word: Option<i64>,
+        parameter: Option<usize>,
+        recent: Vec<(usize, IntCode)>,
+    ) -> Self {
+        ExecutionError {
+            kind,
+            ip,
+            word,
+            parameter,
+            recent,
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The address of the instruction that failed.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// The raw instruction word at `ip`, if it fits in an `i64`.
+    pub fn word(&self) -> Option<i64> {
+        self.word
+    }
+
+    /// Which parameter (counting from 1) of the instruction caused the error, if any one did.
+    pub fn parameter(&self) -> Option<usize> {
+        self.parameter
+    }
+
+    /// The instructions executed leading up to the error, oldest first.
+    pub fn recent_instructions(&self) -> &[(usize, IntCode)] {
+        &self.recent
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at address {}", self.kind, self.ip)?;
+        // Decode errors already name the instruction word, and the parameter if any.
+        let word = match self.kind {
+            ErrorKind::Decode(_) => None,
+            _ => self.word,
+        };
+        if let Some(word) = word {
+            write!(f, " (instruction word {}", word)?;
+            if let Some(parameter) = self.parameter {
+                write!(f, ", parameter {}", parameter)?;
+            }
+            write!(f, ")")?;
+        }
+
+        if !self.recent.is_empty() {
+            write!(f, "\nRecent instructions:")?;
+            for (address, instruction) in &self.recent {
+                write!(f, "\n  {:>6}: {:?}", address, instruction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ExecutionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Why a program couldn't be read from a file.
+#[derive(Debug)]
+pub enum ProgramError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, ParseIntError),
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramError::Io(path, err) => write!(f, "Couldn't read {}: {}", path.display(), err),
+            ProgramError::Parse(path, err) => {
+                write!(f, "{} is not an Intcode program: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProgramError::Io(_, err) => Some(err),
+            ProgramError::Parse(_, err) => Some(err),
+        }
+    }
+}
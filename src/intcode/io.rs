@@ -0,0 +1,274 @@
+//! Devices a `Machine` can be attached to for its input and output.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+use super::{Cell, ExecutionError, Machine, Status, StepResult};
+
+/// Something that supplies a machine's input and receives its output.
+pub trait IoDevice<C: Cell = i64> {
+    /// The next input value, or `None` if there isn't one available, in which case the machine
+    /// pauses until it's run again.
+    fn input(&mut self) -> Option<C>;
+
+    fn output(&mut self, value: C);
+}
+
+impl<C: Cell, D: IoDevice<C> + ?Sized> IoDevice<C> for &mut D {
+    fn input(&mut self) -> Option<C> {
+        (**self).input()
+    }
+
+    fn output(&mut self, value: C) {
+        (**self).output(value)
+    }
+}
+
+impl<C: Cell> Machine<C> {
+    /// Runs the program with its input and output connected to `device`, until it halts or
+    /// needs input that the device doesn't have. Input already queued on the machine is used up
+    /// before the device is asked for any.
+    pub fn run_with<D: IoDevice<C> + ?Sized>(
+        &mut self,
+        device: &mut D,
+    ) -> Result<Status, ExecutionError> {
+        loop {
+            match self.run()? {
+                StepResult::Output(value) => device.output(value),
+                StepResult::NeedsInput => match device.input() {
+                    Some(value) => self.push_input(value),
+                    None => return Ok(Status::AwaitingInput),
+                },
+                StepResult::Halted => return Ok(Status::Halted),
+            }
+        }
+    }
+}
+
+/// Supplies input from a fixed queue and collects output in a vector.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueDevice<C: Cell = i64> {
+    input: VecDeque<C>,
+    output: Vec<C>,
+}
+
+impl<C: Cell> QueueDevice<C> {
+    pub fn new<I: IntoIterator<Item = C>>(input: I) -> Self {
+        QueueDevice {
+            input: input.into_iter().collect(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn push_input(&mut self, value: C) {
+        self.input.push_back(value);
+    }
+
+    pub fn output(&self) -> &[C] {
+        &self.output
+    }
+
+    pub fn into_output(self) -> Vec<C> {
+        self.output
+    }
+}
+
+impl<C: Cell> IoDevice<C> for QueueDevice<C> {
+    fn input(&mut self) -> Option<C> {
+        self.input.pop_front()
+    }
+
+    fn output(&mut self, value: C) {
+        self.output.push(value);
+    }
+}
+
+/// Calls one closure for input and another for output.
+pub struct FnDevice<I, O> {
+    input: I,
+    output: O,
+}
+
+impl<I, O> FnDevice<I, O> {
+    pub fn new<C: Cell>(input: I, output: O) -> Self
+    where
+        I: FnMut() -> Option<C>,
+        O: FnMut(C),
+    {
+        FnDevice { input, output }
+    }
+}
+
+impl<C: Cell, I: FnMut() -> Option<C>, O: FnMut(C)> IoDevice<C> for FnDevice<I, O> {
+    fn input(&mut self) -> Option<C> {
+        (self.input)()
+    }
+
+    fn output(&mut self, value: C) {
+        (self.output)(value)
+    }
+}
+
+/// Receives input from and sends output to other threads.
+///
+/// Waiting for input blocks the machine's thread, and the machine only pauses once every
+/// sender has hung up. Output sent after the receiver has hung up is dropped.
+#[derive(Debug)]
+pub struct ChannelDevice<C: Cell = i64> {
+    input: Receiver<C>,
+    output: Sender<C>,
+}
+
+impl<C: Cell> ChannelDevice<C> {
+    pub fn new(input: Receiver<C>, output: Sender<C>) -> Self {
+        ChannelDevice { input, output }
+    }
+}
+
+impl<C: Cell> IoDevice<C> for ChannelDevice<C> {
+    fn input(&mut self) -> Option<C> {
+        self.input.recv().ok()
+    }
+
+    fn output(&mut self, value: C) {
+        let _ = self.output.send(value);
+    }
+}
+
+/// Prompts for input on stdin, one value per line, and prints output to stdout.
+///
+/// The machine pauses once stdin is closed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdioDevice;
+
+impl<C: Cell> IoDevice<C> for StdioDevice {
+    fn input(&mut self) -> Option<C> {
+        let stdin = io::stdin();
+        loop {
+            print!("> ");
+            io::stdout().flush().ok()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            match line.trim().parse() {
+                Ok(value) => return Some(value),
+                Err(_) => eprintln!("`{}` is not a number", line.trim()),
+            }
+        }
+    }
+
+    fn output(&mut self, value: C) {
+        println!("{}", value);
+    }
+}
+
+/// Passes everything through to another device, keeping a copy of every value in either
+/// direction.
+#[derive(Debug, Clone)]
+pub struct Recorder<D, C: Cell = i64> {
+    device: D,
+    input: Vec<C>,
+    output: Vec<C>,
+}
+
+impl<D: IoDevice<C>, C: Cell> Recorder<D, C> {
+    pub fn new(device: D) -> Self {
+        Recorder {
+            device,
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Every value the machine has read, in order.
+    pub fn input(&self) -> &[C] {
+        &self.input
+    }
+
+    /// Every value the machine has written, in order.
+    pub fn output(&self) -> &[C] {
+        &self.output
+    }
+
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: IoDevice<C>, C: Cell> IoDevice<C> for Recorder<D, C> {
+    fn input(&mut self) -> Option<C> {
+        let value = self.device.input()?;
+        self.input.push(value.clone());
+        Some(value)
+    }
+
+    fn output(&mut self, value: C) {
+        self.output.push(value.clone());
+        self.device.output(value);
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use std::sync::mpsc::channel;
+    #[allow(unused_imports)]
+    use std::thread;
+
+    #[allow(dead_code)]
+    fn doubler() -> Vec<i64> {
+        // Outputs twice every input until it reads a zero.
+        vec![
+            3, 15, 1006, 15, 14, 102, 2, 15, 16, 4, 16, 1105, 1, 0, 99, 0, 0,
+        ]
+    }
+
+    #[test]
+    fn a_queue_device_feeds_and_collects() {
+        let mut machine = Machine::new(doubler());
+        let mut device = QueueDevice::new(vec![1, 2]);
+
+        assert_eq!(
+            machine.run_with(&mut device).unwrap(),
+            Status::AwaitingInput
+        );
+        device.push_input(0);
+        assert_eq!(machine.run_with(&mut device).unwrap(), Status::Halted);
+        assert_eq!(device.into_output(), vec![2, 4]);
+    }
+
+    #[test]
+    fn closures_and_recorders_see_every_value() {
+        let mut inputs = vec![0, 7, 5];
+        let mut outputs = Vec::new();
+        let mut device = Recorder::new(FnDevice::new(|| inputs.pop(), |value| outputs.push(value)));
+
+        assert_eq!(
+            Machine::new(doubler()).run_with(&mut device).unwrap(),
+            Status::Halted
+        );
+        assert_eq!(device.input(), &[5, 7, 0]);
+        assert_eq!(device.output(), &[10, 14]);
+        drop(device);
+        assert_eq!(outputs, vec![10, 14]);
+    }
+
+    #[test]
+    fn a_channel_device_connects_threads() {
+        let (input, receiver) = channel();
+        let (sender, output) = channel();
+        let machine = thread::spawn(move || {
+            let mut device = ChannelDevice::new(receiver, sender);
+            Machine::new(doubler()).run_with(&mut device).unwrap()
+        });
+
+        input.send(21).unwrap();
+        assert_eq!(output.recv().unwrap(), 42);
+        drop(input);
+        assert_eq!(machine.join().unwrap(), Status::AwaitingInput);
+    }
+}
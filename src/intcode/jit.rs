@@ -0,0 +1,493 @@
+//! A compiling tier for the Intcode VM, for brute-force searches that run the same program
+//! over and over.
+//!
+//! Straight-line runs of arithmetic, comparison and jump instructions are compiled into basic
+//! blocks: chains of closures with their opcodes and operands already decoded. Input, output and
+//! halting are left to the interpreter, as is any instruction the compiler can't prove it
+//! handles exactly as the interpreter would.
+//!
+//! Write parameters are still read from memory when the instruction runs, since plenty of
+//! programs (day 2's among them) keep their variables in the write parameters of later
+//! instructions. A write to any other word of a compiled block throws the block away. If the
+//! program wrote it itself, the address is also marked as self-modifying, and from then on always
+//! interpreted.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use super::{
+    flatten_memory, Cell, ErrorKind, Event, ExecutionError, Fault, IntCode, Machine, Memory,
+    OperationMode, Snapshot, Status, StepResult, Tracer,
+};
+
+/// An operand with its mode already applied as far as it can be without running the program.
+#[derive(Debug, Clone)]
+enum Source<C> {
+    Immediate(C),
+    Position(usize),
+    Relative(C),
+}
+
+impl<C: Cell> Source<C> {
+    /// Returns `None` for parameters that would fault, which are left for the interpreter to
+    /// report.
+    fn new(mode: OperationMode, parameter: C) -> Option<Self> {
+        Some(match mode {
+            OperationMode::Immediate => Source::Immediate(parameter),
+            OperationMode::Position => Source::Position(Memory::index(&parameter).ok()?),
+            OperationMode::Relative => Source::Relative(parameter),
+        })
+    }
+
+    fn read(&self, machine: &Machine<C>, parameter: usize) -> Result<C, Fault> {
+        match self {
+            Source::Immediate(value) => Ok(value.clone()),
+            Source::Position(index) => Ok(machine.memory.get(*index)),
+            Source::Relative(offset) => {
+                let address = machine
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(ErrorKind::Overflow)
+                    .map_err(Fault::at(parameter))?;
+                machine.memory.read(&address).map_err(Fault::at(parameter))
+            }
+        }
+    }
+}
+
+/// What a compiled instruction did, as far as the block running it cares.
+enum Flow {
+    Next,
+    Wrote(usize),
+    Jump(usize),
+}
+
+type Operation<C> = Box<dyn Fn(&mut Machine<C>) -> Result<Flow, Fault>>;
+
+struct Instruction<C: Cell> {
+    ip: usize,
+    next: usize,
+    instruction: IntCode,
+    operation: Operation<C>,
+}
+
+struct Block<C: Cell> {
+    start: usize,
+    instructions: Vec<Instruction<C>>,
+    /// The addresses the block was compiled from, leaving out write parameters.
+    code: Vec<usize>,
+}
+
+/// Writes the result of the instruction at `machine.ip` through its third parameter.
+fn store<C: Cell>(machine: &mut Machine<C>, mode: OperationMode, value: C) -> Result<Flow, Fault> {
+    let address = machine.address(3, &mode)?;
+    let index = Memory::index(&address).map_err(Fault::at(3))?;
+    machine.invalidate_decoded(index);
+    machine.memory.set(index, value);
+    Ok(Flow::Wrote(index))
+}
+
+/// Compiles a single instruction from its operand parameters, or returns `None` if it should be
+/// left to the interpreter.
+fn compile<C: Cell + 'static>(instruction: IntCode, operands: Vec<C>) -> Option<Operation<C>> {
+    let modes = instruction.modes();
+    let mut sources = modes
+        .iter()
+        .zip(operands)
+        .map(|(mode, parameter)| Source::new(*mode, parameter))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter();
+    let mut next = || sources.next().unwrap();
+    let destination = modes.last().cloned().unwrap_or(OperationMode::Position);
+
+    Some(match instruction {
+        IntCode::Add(..) | IntCode::Multiply(..) => {
+            let (a, b) = (next(), next());
+            let multiply = matches!(instruction, IntCode::Multiply(..));
+            Box::new(move |machine| {
+                let a = a.read(machine, 1)?;
+                let b = b.read(machine, 2)?;
+                let result = if multiply {
+                    a.checked_mul(&b)
+                } else {
+                    a.checked_add(&b)
+                };
+                let result = result.ok_or(ErrorKind::Overflow)?;
+                store(machine, destination, result)
+            })
+        }
+        IntCode::LessThan(..) | IntCode::Equals(..) => {
+            let (a, b) = (next(), next());
+            let less_than = matches!(instruction, IntCode::LessThan(..));
+            Box::new(move |machine| {
+                let a = a.read(machine, 1)?;
+                let b = b.read(machine, 2)?;
+                let result = if less_than { a < b } else { a == b };
+                store(machine, destination, Machine::flag(result))
+            })
+        }
+        IntCode::JumpIfTrue(..) | IntCode::JumpIfFalse(..) => {
+            let (a, b) = (next(), next());
+            let if_true = matches!(instruction, IntCode::JumpIfTrue(..));
+            Box::new(move |machine| {
+                if a.read(machine, 1)?.is_zero() == if_true {
+                    return Ok(Flow::Next);
+                }
+                let target = b.read(machine, 2)?;
+                Ok(Flow::Jump(Memory::index(&target).map_err(Fault::at(2))?))
+            })
+        }
+        IntCode::AdjustRelativeBase(..) => {
+            let a = next();
+            Box::new(move |machine| {
+                let offset = a.read(machine, 1)?;
+                machine.relative_base = machine
+                    .relative_base
+                    .checked_add(&offset)
+                    .ok_or(ErrorKind::Overflow)
+                    .map_err(Fault::at(1))?;
+                Ok(Flow::Next)
+            })
+        }
+        IntCode::StoreInput(_) | IntCode::LoadOutput(_) | IntCode::Halt => return None,
+    })
+}
+
+/// Collects the addresses written by instructions the interpreter runs.
+struct Writes(Vec<usize>);
+
+impl<C: Cell> Tracer<C> for Writes {
+    fn after(&mut self, event: &Event<C>) {
+        self.0.extend(event.writes.iter().map(|(index, _)| *index));
+    }
+}
+
+/// A `Machine` that compiles the code it runs into closures as it goes.
+///
+/// It produces exactly the same results as a plain `Machine`, errors included.
+pub struct JitMachine<C: Cell = i64> {
+    machine: Machine<C>,
+    blocks: HashMap<usize, Rc<Block<C>>>,
+    /// How many compiled blocks cover each address.
+    covered: Vec<u32>,
+    /// Addresses the program has written to while they were compiled.
+    self_modified: Vec<bool>,
+}
+
+impl<C: Cell + 'static> JitMachine<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        JitMachine::from(Machine::new(program))
+    }
+
+    pub fn machine(&self) -> &Machine<C> {
+        &self.machine
+    }
+
+    pub fn into_machine(self) -> Machine<C> {
+        self.machine
+    }
+
+    pub fn push_input(&mut self, value: C) {
+        self.machine.push_input(value);
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = C>>(&mut self, values: I) {
+        self.machine.extend_input(values);
+    }
+
+    /// Writes a single memory cell, throwing away any compiled code covering it.
+    pub fn set_memory(&mut self, index: usize, value: C) {
+        if self.is_covered(index) {
+            self.invalidate(index);
+        }
+        self.machine.set_memory(index, value);
+    }
+
+    pub fn snapshot(&self) -> Snapshot<C> {
+        self.machine.snapshot()
+    }
+
+    /// Restores the machine to `snapshot`, keeping whatever compiled code is still valid for
+    /// the memory in it.
+    pub fn restore(&mut self, snapshot: &Snapshot<C>) {
+        let mut changed = Vec::new();
+        let covered = &self.covered;
+        self.machine
+            .memory
+            .differences(&snapshot.memory, covered.len(), |index| {
+                if covered[index] > 0 {
+                    changed.push(index);
+                }
+            });
+        for index in changed {
+            if self.is_covered(index) {
+                self.invalidate(index);
+            }
+        }
+        self.machine.restore(snapshot);
+    }
+
+    fn is_covered(&self, index: usize) -> bool {
+        self.covered.get(index).is_some_and(|count| *count > 0)
+    }
+
+    fn is_self_modified(&self, index: usize) -> bool {
+        self.self_modified.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Throws away every block compiled from `index`.
+    fn invalidate(&mut self, index: usize) {
+        let stale = self
+            .blocks
+            .values()
+            .filter(|block| block.code.contains(&index))
+            .map(|block| block.start)
+            .collect::<Vec<_>>();
+        for start in stale {
+            let block = self.blocks.remove(&start).unwrap();
+            for address in &block.code {
+                self.covered[*address] -= 1;
+            }
+        }
+    }
+
+    fn note_write(&mut self, index: usize) {
+        if self.is_covered(index) {
+            self.invalidate(index);
+            if self.self_modified.len() <= index {
+                self.self_modified.resize(index + 1, false);
+            }
+            self.self_modified[index] = true;
+        }
+    }
+
+    /// Compiles as long a block as possible starting at `start`, returning `None` if not even
+    /// the first instruction could be compiled.
+    fn compile_block(&mut self, start: usize) -> Option<Rc<Block<C>>> {
+        let mut instructions = Vec::new();
+        let mut code = Vec::new();
+        let mut ip = start;
+
+        loop {
+            let word = self.machine.memory.get(ip).to_i64();
+            let instruction = match word.and_then(|word| i32::try_from(word).ok()) {
+                Some(word) => match IntCode::try_from(word) {
+                    Ok(instruction) => instruction,
+                    Err(_) => break,
+                },
+                None => break,
+            };
+            let next = ip + instruction.instruction_width();
+            // Code outside the dense region is left to the interpreter, so that what we keep
+            // per address never has to reach out to a distant one.
+            if !self.machine.memory.is_dense(next - 1)
+                || (ip..next).any(|index| self.is_self_modified(index))
+            {
+                break;
+            }
+            let operands = if instruction.writes_to_memory() {
+                ip + 1..next - 1
+            } else {
+                ip + 1..next
+            };
+            let parameters = operands
+                .clone()
+                .map(|index| self.machine.memory.get(index))
+                .collect();
+            let operation = match compile(instruction, parameters) {
+                Some(operation) => operation,
+                None => break,
+            };
+
+            code.push(ip);
+            code.extend(operands);
+
+            instructions.push(Instruction {
+                ip,
+                next,
+                instruction,
+                operation,
+            });
+            ip = next;
+            if let IntCode::JumpIfTrue(..) | IntCode::JumpIfFalse(..) = instruction {
+                break;
+            }
+        }
+
+        if instructions.is_empty() {
+            return None;
+        }
+
+        if self.covered.len() < ip {
+            self.covered.resize(ip, 0);
+        }
+        for address in &code {
+            self.covered[*address] += 1;
+        }
+        let block = Rc::new(Block {
+            start,
+            instructions,
+            code,
+        });
+        self.blocks.insert(start, block.clone());
+        Some(block)
+    }
+
+    /// Whether running `block` could take the machine past its step limit, in which case we
+    /// leave it to the interpreter to stop at exactly the right instruction.
+    fn within_limits(&self, block: &Block<C>) -> bool {
+        let limits = &self.machine.limits;
+        !limits.detect_loops
+            && limits
+                .step_limit
+                .is_none_or(|limit| self.machine.steps + block.instructions.len() as u64 <= limit)
+    }
+
+    fn run_block(&mut self, block: &Block<C>) -> Result<(), ExecutionError> {
+        for instruction in &block.instructions {
+            self.machine
+                .remember(instruction.ip, instruction.instruction);
+            let flow = (instruction.operation)(&mut self.machine)
+                .map_err(|fault| self.machine.error(instruction.ip, fault))?;
+            self.machine.steps += 1;
+            match flow {
+                Flow::Next => self.machine.ip = instruction.next,
+                Flow::Jump(target) => {
+                    self.machine.ip = target;
+                    return Ok(());
+                }
+                Flow::Wrote(index) => {
+                    self.machine.ip = instruction.next;
+                    if self.is_covered(index) {
+                        self.note_write(index);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes instructions until the program produces output, blocks on input or halts, the
+    /// same as `Machine::run`.
+    pub fn run(&mut self) -> Result<StepResult<C>, ExecutionError> {
+        loop {
+            if self.machine.status == Status::Running {
+                let ip = self.machine.ip;
+                let block = match self.blocks.get(&ip) {
+                    Some(block) => Some(block.clone()),
+                    None if !self.is_self_modified(ip) => self.compile_block(ip),
+                    None => None,
+                };
+                if let Some(block) = block {
+                    if self.within_limits(&block) {
+                        self.run_block(&block)?;
+                        continue;
+                    }
+                }
+            }
+
+            let mut writes = Writes(Vec::new());
+            let result = self.machine.step_traced(&mut writes)?;
+            for index in writes.0 {
+                self.note_write(index);
+            }
+            if let Some(result) = result {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Runs the program until it halts, collecting every value it outputs along the way.
+    pub fn run_to_completion(&mut self) -> Result<Vec<C>, ExecutionError> {
+        let mut outputs = Vec::new();
+        loop {
+            match self.run()? {
+                StepResult::Output(value) => outputs.push(value),
+                StepResult::NeedsInput => {
+                    let machine = &self.machine;
+                    return Err(machine.error(machine.ip, ErrorKind::MissingInput.into()));
+                }
+                StepResult::Halted => return Ok(outputs),
+            }
+        }
+    }
+}
+
+impl<C: Cell + 'static> From<Machine<C>> for JitMachine<C> {
+    fn from(machine: Machine<C>) -> Self {
+        JitMachine {
+            machine,
+            blocks: HashMap::new(),
+            covered: Vec::new(),
+            self_modified: Vec::new(),
+        }
+    }
+}
+
+/// The same as `intcode::execute`, but running compiled code where it can.
+pub fn execute<C: Cell + 'static>(
+    memory: &mut Vec<C>,
+    inputs: &[C],
+) -> Result<Vec<C>, ExecutionError> {
+    let mut machine = JitMachine::new(std::mem::take(memory));
+    machine.extend_input(inputs.iter().cloned());
+
+    let result = machine.run_to_completion();
+
+    flatten_memory(machine.into_machine(), memory, result)
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::Limits;
+
+    #[test]
+    fn it_compiles_loops_into_blocks() {
+        // Counts down from 5, adding 3 each time, then outputs the total.
+        let program = vec![
+            1001, 17, 3, 17, 1001, 16, -1, 16, 1005, 16, 0, 4, 17, 99, 0, 0, 5, 0,
+        ];
+        let mut machine = JitMachine::new(program);
+
+        assert_eq!(machine.run().unwrap(), StepResult::Output(15));
+        assert_eq!(machine.blocks.len(), 1);
+        assert_eq!(machine.blocks[&0].instructions.len(), 3);
+        assert_eq!(machine.machine().steps(), 16);
+    }
+
+    #[test]
+    fn self_modifying_writes_fall_back_to_the_interpreter() {
+        // The first instruction rewrites the parameter of the second, from 1 to 7.
+        let mut program = vec![1101, 3, 4, 6, 1101, 0, 1, 12, 4, 12, 99, 0, 0];
+        let expected = crate::intcode::execute(&mut program.clone(), &[]).unwrap();
+
+        let mut machine = JitMachine::new(program.clone());
+        assert_eq!(machine.run_to_completion().unwrap(), expected);
+        assert_eq!(expected, vec![7]);
+        assert!(machine.is_self_modified(6));
+        assert!(!machine.is_self_modified(0));
+
+        assert_eq!(execute(&mut program, &[]).unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn step_limits_stop_at_the_same_instruction() {
+        let program = vec![1101, 1, 1, 5, 1105, 1, 0];
+        let limits = Limits {
+            step_limit: Some(5),
+            ..Limits::default()
+        };
+
+        let mut interpreted = Machine::new(program.clone()).with_limits(limits);
+        let mut compiled = JitMachine::from(Machine::new(program).with_limits(limits));
+
+        let expected = interpreted.run().unwrap_err();
+        let err = compiled.run().unwrap_err();
+        assert_eq!(err.kind(), expected.kind());
+        assert_eq!(err.ip(), expected.ip());
+    }
+}
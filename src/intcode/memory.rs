@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use super::{Cell, ErrorKind};
+
+/// How far past the end of the dense region a write may land and still grow it, rather than
+/// being stored in the sparse overflow map.
+const DENSE_GROWTH_LIMIT: usize = 4096;
+
+/// How many cells make up one copy-on-write page of the dense region.
+const PAGE_SIZE: usize = 256;
+
+/// How many cells `to_vec` is willing to allocate beyond the dense region.
+const FLATTEN_LIMIT: usize = 1 << 24;
+
+/// Intcode memory, in which every non-negative address is valid and starts out as zero.
+///
+/// The program image and anything written close to it live in a dense region. Writes far
+/// beyond it go into a sparse map so that a program poking at a huge address doesn't force us
+/// to allocate everything in between.
+///
+/// The dense region is split into reference counted pages which are only copied when written
+/// to, so cloning memory is cheap and clones share every page neither of them has modified.
+#[derive(Debug, Clone)]
+pub struct Memory<C: Cell = i64> {
+    pages: Vec<Arc<Vec<C>>>,
+    /// How many cells of the dense region are in use. Every cell past it is zero.
+    dense_len: usize,
+    sparse: HashMap<usize, C>,
+}
+
+impl<C: Cell> Memory<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        let dense_len = program.len();
+        let pages = program
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = chunk.to_vec();
+                page.resize(PAGE_SIZE, C::zero());
+                Arc::new(page)
+            })
+            .collect();
+
+        Memory {
+            pages,
+            dense_len,
+            sparse: HashMap::new(),
+        }
+    }
+
+    /// Converts a cell used as an address into an index into memory.
+    pub fn index(address: &C) -> Result<usize, ErrorKind> {
+        let address = address.to_i64().ok_or(ErrorKind::Conversion)?;
+        if address < 0 {
+            return Err(ErrorKind::NegativeAddress);
+        }
+        Ok(usize::try_from(address)?)
+    }
+
+    pub fn read(&self, address: &C) -> Result<C, ErrorKind> {
+        Ok(self.get(Self::index(address)?))
+    }
+
+    pub fn write(&mut self, address: &C, value: C) -> Result<(), ErrorKind> {
+        self.set(Self::index(address)?, value);
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> C {
+        if index < self.dense_len {
+            self.pages[index / PAGE_SIZE][index % PAGE_SIZE].clone()
+        } else {
+            self.sparse.get(&index).cloned().unwrap_or_else(C::zero)
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: C) {
+        if index < self.dense_len {
+            self.set_dense(index, value);
+        } else if index < self.dense_len + DENSE_GROWTH_LIMIT {
+            self.grow_dense(index + 1);
+            self.set_dense(index, value);
+        } else {
+            self.sparse.insert(index, value);
+        }
+    }
+
+    fn set_dense(&mut self, index: usize, value: C) {
+        let page = &mut self.pages[index / PAGE_SIZE];
+        if page[index % PAGE_SIZE] != value {
+            Arc::make_mut(page)[index % PAGE_SIZE] = value;
+        }
+    }
+
+    fn grow_dense(&mut self, len: usize) {
+        let start = self.dense_len;
+        let pages = len.div_ceil(PAGE_SIZE);
+        if pages > self.pages.len() {
+            let zeros = Arc::new(vec![C::zero(); PAGE_SIZE]);
+            self.pages.resize(pages, zeros);
+        }
+        self.dense_len = len;
+
+        if !self.sparse.is_empty() {
+            for index in start..len {
+                if let Some(value) = self.sparse.remove(&index) {
+                    self.set_dense(index, value);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` with every address below `limit` at which `self` and `other` differ. Pages the
+    /// two share are skipped without being looked at.
+    pub(super) fn differences(&self, other: &Memory<C>, limit: usize, mut f: impl FnMut(usize)) {
+        let shared = self.pages.len().min(other.pages.len());
+        for (page, (ours, theirs)) in self.pages.iter().zip(&other.pages).enumerate() {
+            if page * PAGE_SIZE >= limit {
+                return;
+            }
+            if Arc::ptr_eq(ours, theirs) {
+                continue;
+            }
+            let len = PAGE_SIZE.min(limit - page * PAGE_SIZE);
+            for (offset, (a, b)) in ours[..len].iter().zip(&theirs[..len]).enumerate() {
+                if a != b {
+                    f(page * PAGE_SIZE + offset);
+                }
+            }
+        }
+        for index in shared * PAGE_SIZE..limit {
+            if self.get(index) != other.get(index) {
+                f(index);
+            }
+        }
+    }
+
+    /// Whether `index` is in the dense region, rather than stored sparsely or not at all.
+    pub(super) fn is_dense(&self, index: usize) -> bool {
+        index < self.dense_len
+    }
+
+    /// One past the highest address that has ever been part of the program or written to.
+    pub fn len(&self) -> usize {
+        self.sparse
+            .keys()
+            .map(|index| index + 1)
+            .max()
+            .unwrap_or(0)
+            .max(self.dense_len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn dense(&self) -> impl Iterator<Item = &C> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter())
+            .take(self.dense_len)
+    }
+
+    /// Every cell that has been part of the program or written to, in order of address.
+    pub fn cells(&self) -> impl Iterator<Item = (usize, &C)> {
+        let mut sparse = self
+            .sparse
+            .iter()
+            .map(|(index, value)| (*index, value))
+            .collect::<Vec<_>>();
+        sparse.sort_by_key(|(index, _)| *index);
+        self.dense().enumerate().chain(sparse)
+    }
+
+    /// Flattens memory into a vector covering every address up to `len`. Fails if a sparse
+    /// write landed so far out that the vector would be unreasonably large.
+    pub fn to_vec(&self) -> Result<Vec<C>, ErrorKind> {
+        let len = self.len();
+        if len > self.dense_len.max(FLATTEN_LIMIT) {
+            return Err(ErrorKind::MemoryTooLarge { address: len - 1 });
+        }
+        let mut memory = self.dense().cloned().collect::<Vec<_>>();
+        memory.resize(len, C::zero());
+        for (index, value) in &self.sparse {
+            memory[*index] = value.clone();
+        }
+        Ok(memory)
+    }
+}
+
+/// Memories are equal if every address holds the same value in both, however they're laid out.
+impl<C: Cell> PartialEq for Memory<C> {
+    fn eq(&self, other: &Self) -> bool {
+        let mut equal = true;
+        self.differences(other, self.dense_len.max(other.dense_len), |_| {
+            equal = false
+        });
+        equal
+            && self
+                .sparse
+                .iter()
+                .all(|(index, value)| other.get(*index) == *value)
+            && other
+                .sparse
+                .iter()
+                .all(|(index, value)| self.get(*index) == *value)
+    }
+}
+
+/// Only the non-zero cells are hashed, along with their addresses, so memories that are equal
+/// hash the same however their cells are split between the dense region and the sparse map.
+impl<C: Cell> Hash for Memory<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for (index, value) in self.cells().filter(|(_, value)| !value.is_zero()) {
+            index.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+impl<C: Cell> From<Vec<C>> for Memory<C> {
+    fn from(program: Vec<C>) -> Self {
+        Memory::new(program)
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use std::collections::hash_map::DefaultHasher;
+
+    #[test]
+    fn unwritten_addresses_read_as_zero() {
+        let memory = Memory::new(vec![1_i64, 2, 3]);
+
+        assert_eq!(memory.read(&1).unwrap(), 2);
+        assert_eq!(memory.read(&3).unwrap(), 0);
+        assert_eq!(memory.read(&1_000_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn negative_addresses_are_rejected() {
+        let mut memory = Memory::new(vec![1_i64, 2, 3]);
+
+        assert_eq!(memory.read(&-1), Err(ErrorKind::NegativeAddress));
+        assert_eq!(memory.write(&-1, 5), Err(ErrorKind::NegativeAddress));
+    }
+
+    #[test]
+    fn writes_near_the_program_grow_it_and_distant_writes_are_sparse() {
+        let mut memory = Memory::new(vec![1_i64, 2, 3]);
+
+        memory.write(&5, 8).unwrap();
+        memory.write(&1_000_000, 9).unwrap();
+
+        assert_eq!(memory.dense_len, 6);
+        assert_eq!(memory.sparse.len(), 1);
+        assert_eq!(memory.read(&5).unwrap(), 8);
+        assert_eq!(memory.read(&1_000_000).unwrap(), 9);
+        assert_eq!(memory.len(), 1_000_001);
+    }
+
+    #[test]
+    fn growing_dense_memory_absorbs_sparse_entries() {
+        let mut memory = Memory::new(vec![1_i64]);
+
+        memory.set(5000, 7);
+        for index in 1..=4500 {
+            memory.set(index, 1);
+        }
+        memory.set(5001, 2);
+
+        assert!(memory.sparse.is_empty());
+        assert_eq!(memory.get(5000), 7);
+        assert_eq!(memory.to_vec().unwrap().len(), 5002);
+    }
+
+    #[test]
+    fn distant_writes_cannot_be_flattened() {
+        let mut memory = Memory::new(vec![1_i64, 2, 3]);
+
+        memory.set(1_000_000_000_000, 4);
+
+        assert_eq!(
+            memory.to_vec(),
+            Err(ErrorKind::MemoryTooLarge {
+                address: 1_000_000_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn memories_with_the_same_contents_are_equal() {
+        let mut dense = Memory::new(vec![1_i64, 0, 0]);
+        let mut sparse = Memory::new(vec![1_i64]);
+
+        dense.set(100_000, 5);
+        sparse.set(100_000, 5);
+        assert_eq!(dense, sparse);
+
+        sparse.set(2, 3);
+        assert_ne!(dense, sparse);
+    }
+
+    #[test]
+    fn equal_memories_hash_the_same() {
+        let hash = |memory: &Memory<i64>| {
+            let mut hasher = DefaultHasher::new();
+            memory.hash(&mut hasher);
+            hasher.finish()
+        };
+        let mut a = Memory::new(vec![1_i64]);
+        let mut b = Memory::new(vec![1_i64]);
+
+        a.set(5000, 7);
+        b.set(4000, 0);
+        b.set(5000, 7);
+
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn clones_share_pages_until_they_are_written_to() {
+        let original = Memory::new(vec![0_i64; 1000]);
+        let mut copy = original.clone();
+
+        copy.set(300, 1);
+
+        assert!(Arc::ptr_eq(&original.pages[0], &copy.pages[0]));
+        assert!(!Arc::ptr_eq(&original.pages[1], &copy.pages[1]));
+        assert_eq!(original.get(300), 0);
+        assert_eq!(copy.get(300), 1);
+    }
+}
@@ -0,0 +1,316 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use super::{Cell, ExecutionError, Machine, StepResult};
+
+/// How a machine in a `Network` ended up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Halted,
+    /// The machine was waiting for input when every other machine had either stopped or was
+    /// waiting too, so none ever would have arrived.
+    Blocked,
+    Failed(ExecutionError),
+}
+
+/// What a `Network` did, machine by machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report<C: Cell = i64> {
+    outcomes: Vec<Outcome>,
+    outputs: Vec<Vec<C>>,
+}
+
+impl<C: Cell> Report<C> {
+    pub fn outcome(&self, machine: usize) -> &Outcome {
+        &self.outcomes[machine]
+    }
+
+    /// Everything `machine` output, whether or not it was sent on to another machine.
+    pub fn outputs(&self, machine: usize) -> &[C] {
+        &self.outputs[machine]
+    }
+
+    /// The machines that were left waiting for input.
+    pub fn blocked(&self) -> Vec<usize> {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| **outcome == Outcome::Blocked)
+            .map(|(machine, _)| machine)
+            .collect()
+    }
+
+    pub fn is_deadlocked(&self) -> bool {
+        self.outcomes.contains(&Outcome::Blocked)
+    }
+}
+
+/// A set of machines all running the same program, each on its own thread, with the output of
+/// one wired to the input of others.
+///
+/// A machine with several successors sends each of them a copy of every value it outputs, and
+/// one with several predecessors receives their values in whatever order they arrive.
+#[derive(Debug, Clone)]
+pub struct Network<C: Cell = i64> {
+    program: Vec<C>,
+    inputs: Vec<Vec<C>>,
+    successors: Vec<Vec<usize>>,
+}
+
+impl<C: Cell + Send + Sync> Network<C> {
+    pub fn new(program: Vec<C>) -> Self {
+        Network {
+            program,
+            inputs: Vec::new(),
+            successors: Vec::new(),
+        }
+    }
+
+    /// Machines connected one after another, each seeded with its own initial input.
+    pub fn chain(program: Vec<C>, inputs: Vec<Vec<C>>) -> Self {
+        let mut network = Network::new(program);
+        for input in inputs {
+            network.add_machine(input);
+        }
+        for machine in 1..network.len() {
+            network.connect(machine - 1, machine);
+        }
+        network
+    }
+
+    /// Like `chain`, but with the last machine's output fed back into the first.
+    pub fn ring(program: Vec<C>, inputs: Vec<Vec<C>>) -> Self {
+        let mut network = Network::chain(program, inputs);
+        if !network.is_empty() {
+            network.connect(network.len() - 1, 0);
+        }
+        network
+    }
+
+    /// Adds a machine that starts out with `input` queued, returning its index.
+    pub fn add_machine(&mut self, input: Vec<C>) -> usize {
+        self.inputs.push(input);
+        self.successors.push(Vec::new());
+        self.inputs.len() - 1
+    }
+
+    /// Sends everything `from` outputs to `to`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        assert!(to < self.len(), "No machine {} to connect to", to);
+        self.successors[from].push(to);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Runs every machine until it halts, fails, or the network deadlocks.
+    pub fn run(self) -> Report<C> {
+        let Network {
+            program,
+            inputs,
+            successors,
+        } = self;
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..inputs.len()).map(|_| channel()).unzip();
+        let monitor = Monitor {
+            state: Mutex::new(MonitorState {
+                states: vec![State::Running; inputs.len()],
+                pending: vec![0; inputs.len()],
+            }),
+            senders,
+        };
+
+        let program = &program;
+        let successors = &successors;
+        let monitor = &monitor;
+        let results = thread::scope(|scope| {
+            let handles = inputs
+                .into_iter()
+                .zip(receivers)
+                .enumerate()
+                .map(|(id, (input, receiver))| {
+                    let mut machine = Machine::new(program.clone());
+                    machine.extend_input(input);
+                    let node = Node {
+                        id,
+                        machine,
+                        receiver,
+                        successors: &successors[id],
+                        monitor,
+                    };
+                    scope.spawn(move || node.run())
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Network thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let (outcomes, outputs) = results.into_iter().unzip();
+        Report { outcomes, outputs }
+    }
+}
+
+enum Message<C> {
+    Value(C),
+    /// The network has deadlocked and the receiving machine should give up waiting.
+    Stop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Running,
+    Waiting,
+    Stopped,
+}
+
+struct MonitorState {
+    states: Vec<State>,
+    /// How many values have been sent to each machine but not yet received.
+    pending: Vec<usize>,
+}
+
+/// Keeps track of which machines are waiting for input, so that we can tell when all of them
+/// are and no input is on its way.
+struct Monitor<C> {
+    state: Mutex<MonitorState>,
+    senders: Vec<Sender<Message<C>>>,
+}
+
+impl<C> Monitor<C> {
+    fn send(&self, to: usize, value: C) {
+        self.state.lock().unwrap().pending[to] += 1;
+        // The receiver may have stopped already, in which case nobody cares about the value.
+        let _ = self.senders[to].send(Message::Value(value));
+    }
+
+    fn received(&self, id: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.pending[id] -= 1;
+        state.states[id] = State::Running;
+    }
+
+    fn set_state(&self, id: usize, new_state: State) {
+        let mut state = self.state.lock().unwrap();
+        state.states[id] = new_state;
+
+        let deadlocked =
+            state
+                .states
+                .iter()
+                .zip(&state.pending)
+                .all(|(state, pending)| match state {
+                    State::Running => false,
+                    State::Waiting => *pending == 0,
+                    State::Stopped => true,
+                });
+        if deadlocked {
+            for (id, state) in state.states.iter().enumerate() {
+                if *state == State::Waiting {
+                    let _ = self.senders[id].send(Message::Stop);
+                }
+            }
+        }
+    }
+}
+
+struct Node<'a, C: Cell> {
+    id: usize,
+    machine: Machine<C>,
+    receiver: Receiver<Message<C>>,
+    successors: &'a [usize],
+    monitor: &'a Monitor<C>,
+}
+
+impl<'a, C: Cell> Node<'a, C> {
+    fn run(mut self) -> (Outcome, Vec<C>) {
+        let mut outputs = Vec::new();
+        let outcome = loop {
+            match self.machine.run() {
+                Ok(StepResult::Output(value)) => {
+                    for &successor in self.successors {
+                        self.monitor.send(successor, value.clone());
+                    }
+                    outputs.push(value);
+                }
+                Ok(StepResult::NeedsInput) => {
+                    self.monitor.set_state(self.id, State::Waiting);
+                    match self.receiver.recv() {
+                        Ok(Message::Value(value)) => {
+                            self.monitor.received(self.id);
+                            self.machine.push_input(value);
+                        }
+                        Ok(Message::Stop) | Err(_) => break Outcome::Blocked,
+                    }
+                }
+                Ok(StepResult::Halted) => break Outcome::Halted,
+                Err(err) => break Outcome::Failed(err),
+            }
+        };
+        self.monitor.set_state(self.id, State::Stopped);
+        (outcome, outputs)
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[allow(dead_code)]
+    fn amplifier() -> Vec<i64> {
+        // Reads a phase setting, then outputs each further input plus the phase until it reads
+        // a zero.
+        vec![
+            3, 19, 3, 20, 1006, 20, 18, 1, 19, 20, 20, 4, 20, 1105, 1, 2, 0, 0, 99, 0, 0,
+        ]
+    }
+
+    #[test]
+    fn a_chain_passes_values_along_and_halts() {
+        let network = Network::chain(amplifier(), vec![vec![1, 5, 7, 0], vec![10], vec![100]]);
+
+        let report = network.run();
+
+        assert_eq!(report.outputs(0), &[6, 8]);
+        assert_eq!(report.outputs(2), &[116, 118]);
+        assert_eq!(report.outcome(0), &Outcome::Halted);
+        assert_eq!(report.blocked(), vec![1, 2]);
+    }
+
+    #[test]
+    fn a_ring_with_no_way_to_start_is_deadlocked() {
+        let network = Network::ring(amplifier(), vec![vec![1], vec![2], vec![3]]);
+
+        let report = network.run();
+
+        assert!(report.is_deadlocked());
+        assert_eq!(report.blocked(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn arbitrary_graphs_broadcast_and_merge() {
+        let mut network = Network::new(amplifier());
+        let source = network.add_machine(vec![0, 1, 2, 0]);
+        let left = network.add_machine(vec![10]);
+        let right = network.add_machine(vec![20]);
+        let sink = network.add_machine(vec![0]);
+        network.connect(source, left);
+        network.connect(source, right);
+        network.connect(left, sink);
+        network.connect(right, sink);
+
+        let report = network.run();
+
+        let mut merged = report.outputs(sink).to_vec();
+        merged.sort_unstable();
+        assert_eq!(merged, vec![11, 12, 21, 22]);
+        assert_eq!(report.blocked(), vec![left, right, sink]);
+    }
+}
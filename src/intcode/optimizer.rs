@@ -0,0 +1,508 @@
+//! A peephole optimiser for Intcode programs, and a checker to go with it.
+//!
+//! Instructions whose operands are known before the program runs, either because they're
+//! immediate or because they're read from cells that never change, are rewritten into
+//! constant stores (`ADD #k, #0 -> [d]`), and writes of a value a cell already always holds
+//! are skipped over entirely.
+//!
+//! It is deliberately conservative. Rewriting code is only safe when every read and write in
+//! the program is known, so anything using relative mode or jumping through memory is left
+//! alone, as is any instruction whose words the program itself reads or writes.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use super::analysis::analyze;
+use super::disassembler::{format_instruction, Entry};
+use super::{execute, Cell, ErrorKind, IntCode, Machine, Memory, OperationMode};
+
+/// Why an instruction was rewritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Its result was worked out ahead of time and it now stores that constant.
+    ConstantFolded,
+    /// Operands read from cells that never change are now immediate.
+    ImmediateOperands,
+    /// It only ever wrote the value already in its destination, so it's now jumped over.
+    RedundantWrite,
+    /// A jump that's always taken now says so.
+    ConstantCondition,
+}
+
+/// A single instruction the optimiser replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rewrite<C: Cell = i64> {
+    pub address: usize,
+    pub original: Vec<C>,
+    pub replacement: Vec<C>,
+    pub reason: Reason,
+}
+
+impl<C: Cell> fmt::Display for Rewrite<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let describe = |words: &[C]| match super::disassembler::decode_at(words, 0) {
+            Some((instruction, parameters)) => format_instruction(&instruction, &parameters),
+            None => "?".to_owned(),
+        };
+        write!(
+            f,
+            "{}: {}  =>  {}  ({:?})",
+            self.address,
+            describe(&self.original),
+            describe(&self.replacement),
+            self.reason
+        )
+    }
+}
+
+/// An optimised program, along with what was done to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Optimized<C: Cell = i64> {
+    program: Vec<C>,
+    rewrites: Vec<Rewrite<C>>,
+}
+
+impl<C: Cell> Optimized<C> {
+    pub fn program(&self) -> &[C] {
+        &self.program
+    }
+
+    pub fn into_program(self) -> Vec<C> {
+        self.program
+    }
+
+    pub fn rewrites(&self) -> &[Rewrite<C>] {
+        &self.rewrites
+    }
+
+    /// Whether `address` is part of an instruction that was rewritten.
+    fn is_rewritten(&self, address: usize) -> bool {
+        self.rewrites.iter().any(|rewrite| {
+            (rewrite.address..rewrite.address + rewrite.original.len()).contains(&address)
+        })
+    }
+}
+
+/// An instruction along with where it is.
+struct Located<C> {
+    address: usize,
+    instruction: IntCode,
+    parameters: Vec<C>,
+}
+
+impl<C: Cell> Located<C> {
+    /// The address the instruction writes to, if it writes to one.
+    fn destination(&self) -> Option<usize> {
+        if self.instruction.writes_to_memory() {
+            Memory::index(self.parameters.last()?).ok()
+        } else {
+            None
+        }
+    }
+
+    /// The operands the instruction reads, as `(parameter index, mode)`.
+    fn operands(&self) -> impl Iterator<Item = (usize, OperationMode)> {
+        let mut modes = self.instruction.modes();
+        if self.instruction.writes_to_memory() {
+            modes.pop();
+        }
+        modes.into_iter().enumerate()
+    }
+}
+
+/// What's known about a program's memory before it runs.
+struct Facts<'a, C: Cell> {
+    program: &'a [C],
+    /// The instructions writing to each address.
+    writers: BTreeMap<usize, Vec<usize>>,
+    /// Every address read by some instruction.
+    reads: BTreeSet<usize>,
+    /// The value each instruction with a known result writes, by instruction address.
+    results: HashMap<usize, C>,
+}
+
+impl<'a, C: Cell> Facts<'a, C> {
+    /// The value of a cell, if it's the same throughout the program's run.
+    fn constant(&self, address: usize) -> Option<C> {
+        let value = self.program.get(address).cloned().unwrap_or_else(C::zero);
+        let writers = self.writers.get(&address).map_or(&[][..], |w| &w[..]);
+        if writers
+            .iter()
+            .all(|writer| self.results.get(writer) == Some(&value))
+        {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn operand(&self, located: &Located<C>, index: usize, mode: OperationMode) -> Option<C> {
+        let parameter = &located.parameters[index];
+        match mode {
+            OperationMode::Immediate => Some(parameter.clone()),
+            OperationMode::Position => self.constant(Memory::index(parameter).ok()?),
+            OperationMode::Relative => None,
+        }
+    }
+
+    fn result(&self, located: &Located<C>) -> Option<C> {
+        let mut operands = located
+            .operands()
+            .map(|(index, mode)| self.operand(located, index, mode));
+        let a = operands.next()??;
+        let b = operands.next()??;
+        match located.instruction {
+            IntCode::Add(..) => a.checked_add(&b),
+            IntCode::Multiply(..) => a.checked_mul(&b),
+            IntCode::LessThan(..) => Some(Machine::flag(a < b)),
+            IntCode::Equals(..) => Some(Machine::flag(a == b)),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes an instruction with the given parameter modes.
+fn encode<C: Cell>(opcode: i32, modes: &[OperationMode]) -> C {
+    let digits = modes
+        .iter()
+        .rev()
+        .fold(0, |digits, mode| digits * 10 + mode.digit());
+    C::from_i64(i64::from(digits * 100 + opcode))
+}
+
+fn rewrite<C: Cell>(located: &Located<C>, facts: &Facts<C>) -> Option<(Vec<C>, Reason)> {
+    let instruction = &located.instruction;
+    let modes = instruction.modes();
+
+    if let Some(value) = facts.result(located) {
+        let destination = located.destination()?;
+        if facts.constant(destination).as_ref() == Some(&value) {
+            let next = located.address + instruction.instruction_width();
+            let jump = vec![
+                encode(6, &[OperationMode::Immediate, OperationMode::Immediate]),
+                C::zero(),
+                C::from_i64(next as i64),
+            ];
+            return Some((jump, Reason::RedundantWrite));
+        }
+        let store = vec![
+            encode(
+                1,
+                &[OperationMode::Immediate, OperationMode::Immediate, modes[2]],
+            ),
+            value,
+            C::zero(),
+            located.parameters[2].clone(),
+        ];
+        return Some((store, Reason::ConstantFolded));
+    }
+
+    if let IntCode::JumpIfTrue(..) | IntCode::JumpIfFalse(..) = instruction {
+        let condition = facts.operand(located, 0, modes[0]);
+        let target = facts.operand(located, 1, modes[1]);
+        let jump_if_true = matches!(instruction, IntCode::JumpIfTrue(..));
+        if let (Some(condition), Some(target)) = (condition, target) {
+            if condition.is_zero() != jump_if_true {
+                let jump = vec![
+                    encode(5, &[OperationMode::Immediate, OperationMode::Immediate]),
+                    C::one(),
+                    target,
+                ];
+                return Some((jump, Reason::ConstantCondition));
+            }
+        }
+    }
+
+    let mut modes = modes;
+    let mut words = vec![C::zero()];
+    words.extend(located.parameters.iter().cloned());
+    let mut changed = false;
+    for (index, mode) in located.operands() {
+        if mode != OperationMode::Position {
+            continue;
+        }
+        if let Some(value) = facts.operand(located, index, mode) {
+            modes[index] = OperationMode::Immediate;
+            words[index + 1] = value;
+            changed = true;
+        }
+    }
+    if !changed {
+        return None;
+    }
+    words[0] = encode(instruction.opcode(), &modes);
+    Some((words, Reason::ImmediateOperands))
+}
+
+/// Optimises a program, returning it unchanged if it can't be analysed well enough to be sure
+/// of preserving what it does.
+pub fn optimize<C: Cell>(program: &[C]) -> Optimized<C> {
+    let unchanged = Optimized {
+        program: program.to_vec(),
+        rewrites: Vec::new(),
+    };
+
+    let analysis = analyze(program);
+    if !analysis.indirect_jumps().is_empty() || !analysis.invalid().is_empty() {
+        return unchanged;
+    }
+    let code = analysis
+        .blocks()
+        .flat_map(|block| block.instructions())
+        .filter_map(|entry| match entry {
+            Entry::Instruction {
+                address,
+                instruction,
+                parameters,
+            } => Some(Located {
+                address: *address,
+                instruction: *instruction,
+                parameters: parameters.clone(),
+            }),
+            Entry::Data { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Everything has to be at a fixed address, and no two instructions may share a word.
+    let mut owners = BTreeMap::new();
+    for located in &code {
+        if located
+            .instruction
+            .modes()
+            .contains(&OperationMode::Relative)
+        {
+            return unchanged;
+        }
+        let width = located.instruction.instruction_width();
+        for address in located.address..located.address + width {
+            if owners.insert(address, located.address).is_some() {
+                return unchanged;
+            }
+        }
+    }
+
+    let mut facts = Facts {
+        program,
+        writers: BTreeMap::new(),
+        reads: BTreeSet::new(),
+        results: HashMap::new(),
+    };
+    for located in &code {
+        if located.instruction.writes_to_memory() {
+            match located.destination() {
+                Some(destination) => facts
+                    .writers
+                    .entry(destination)
+                    .or_default()
+                    .push(located.address),
+                None => return unchanged,
+            }
+        }
+        for (index, mode) in located.operands() {
+            if mode == OperationMode::Position {
+                match Memory::index(&located.parameters[index]) {
+                    Ok(address) => facts.reads.insert(address),
+                    Err(_) => return unchanged,
+                };
+            }
+        }
+    }
+
+    // Knowing one instruction's result can make another's operands constant, so keep going
+    // until nothing new is learnt.
+    loop {
+        let learnt = code
+            .iter()
+            .filter(|located| !facts.results.contains_key(&located.address))
+            .filter_map(|located| Some((located.address, facts.result(located)?)))
+            .collect::<Vec<_>>();
+        if learnt.is_empty() {
+            break;
+        }
+        facts.results.extend(learnt);
+    }
+
+    let mut optimized = unchanged;
+    for located in &code {
+        let width = located.instruction.instruction_width();
+        let words = located.address..located.address + width;
+        if words
+            .clone()
+            .any(|address| facts.reads.contains(&address) || facts.writers.contains_key(&address))
+        {
+            continue;
+        }
+        if let Some((replacement, reason)) = rewrite(located, &facts) {
+            let original = program[words].to_vec();
+            if replacement[..] == original[..replacement.len()] {
+                continue;
+            }
+            optimized.program[located.address..located.address + replacement.len()]
+                .clone_from_slice(&replacement);
+            optimized.rewrites.push(Rewrite {
+                address: located.address,
+                original,
+                replacement,
+                reason,
+            });
+        }
+    }
+    optimized
+}
+
+/// How an optimised program behaved differently from the original.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference<C: Cell = i64> {
+    Output {
+        expected: Result<Vec<C>, ErrorKind>,
+        actual: Result<Vec<C>, ErrorKind>,
+    },
+    Memory {
+        address: usize,
+        expected: C,
+        actual: C,
+    },
+}
+
+/// The first sample input an optimised program got wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch<C: Cell = i64> {
+    pub inputs: Vec<C>,
+    pub difference: Difference<C>,
+}
+
+/// Runs the original and optimised programs side by side on every set of sample inputs,
+/// checking that they produce the same output, or fail in the same way, and leave the same
+/// values in memory outside the rewritten instructions.
+pub fn check<C: Cell>(
+    original: &[C],
+    optimized: &Optimized<C>,
+    samples: &[Vec<C>],
+) -> Result<(), Mismatch<C>> {
+    for inputs in samples {
+        let mismatch = |difference| Mismatch {
+            inputs: inputs.clone(),
+            difference,
+        };
+
+        let mut expected_memory = original.to_vec();
+        let mut actual_memory = optimized.program.clone();
+        let expected = execute(&mut expected_memory, inputs).map_err(|err| err.kind().clone());
+        let actual = execute(&mut actual_memory, inputs).map_err(|err| err.kind().clone());
+        if expected != actual {
+            return Err(mismatch(Difference::Output { expected, actual }));
+        }
+
+        let len = expected_memory.len().max(actual_memory.len());
+        for address in (0..len).filter(|address| !optimized.is_rewritten(*address)) {
+            let expected = expected_memory
+                .get(address)
+                .cloned()
+                .unwrap_or_else(C::zero);
+            let actual = actual_memory.get(address).cloned().unwrap_or_else(C::zero);
+            if expected != actual {
+                return Err(mismatch(Difference::Memory {
+                    address,
+                    expected,
+                    actual,
+                }));
+            }
+        }
+    }
+    Ok(())
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn it_folds_constants_and_propagates_them() {
+        // [13] = 2 * 3, then output [13] + [14], where [14] never changes. [13] does change,
+        // from 0 to 6, so it can't be read ahead of time.
+        let program = vec![1102_i64, 2, 3, 13, 1, 13, 14, 15, 4, 15, 99, 0, 0, 0, 4, 0];
+        let optimized = optimize(&program);
+
+        assert_eq!(
+            optimized
+                .rewrites()
+                .iter()
+                .map(|rewrite| rewrite.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "0: MUL #2, #3 -> [13]  =>  ADD #6, #0 -> [13]  (ConstantFolded)",
+                "4: ADD [13], [14] -> [15]  =>  ADD [13], #4 -> [15]  (ImmediateOperands)",
+            ]
+        );
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn it_skips_writes_of_values_already_there() {
+        let program = vec![1101_i64, 2, 3, 9, 4, 9, 99, 0, 0, 5];
+        let optimized = optimize(&program);
+
+        assert_eq!(optimized.rewrites()[0].reason, Reason::RedundantWrite);
+        assert_eq!(&optimized.program()[..4], &[1106, 0, 4, 9]);
+        assert_eq!(
+            execute(&mut optimized.into_program(), &[]).unwrap(),
+            vec![5]
+        );
+    }
+
+    #[test]
+    fn it_leaves_code_the_program_reads_or_writes_alone() {
+        // Day 2's first example reads the parameters of its first instruction as data.
+        let program = vec![1_i64, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+        let optimized = optimize(&program);
+
+        assert!(optimized
+            .rewrites()
+            .iter()
+            .all(|rewrite| rewrite.address != 0));
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn it_keeps_input_dependent_values() {
+        // Outputs whether the input equals [10], which never changes.
+        let program = vec![3_i64, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let optimized = optimize(&program);
+
+        assert_eq!(optimized.program(), &[3, 9, 1008, 9, 8, 9, 4, 9, 99, -1, 8]);
+        let samples = (0..10).map(|input| vec![input]).collect::<Vec<_>>();
+        assert_eq!(check(&program, &optimized, &samples), Ok(()));
+    }
+
+    #[test]
+    fn day2_behaves_the_same_once_optimised() {
+        let mut program =
+            crate::day2::generate_input(include_str!("../../input/2019/day2.txt").trim());
+        program[1] = 12;
+        program[2] = 2;
+        let optimized = optimize(&program);
+
+        assert!(!optimized.rewrites().is_empty());
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn the_checker_catches_broken_rewrites() {
+        let program = vec![104_i64, 1, 99];
+        let broken = Optimized {
+            program: vec![104, 2, 99],
+            rewrites: Vec::new(),
+        };
+
+        assert_eq!(
+            check(&program, &broken, &[vec![]]),
+            Err(Mismatch {
+                inputs: vec![],
+                difference: Difference::Output {
+                    expected: Ok(vec![1]),
+                    actual: Ok(vec![2]),
+                },
+            })
+        );
+    }
+}
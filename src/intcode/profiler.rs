@@ -0,0 +1,246 @@
+//! Finding out where an Intcode program spends its time.
+//!
+//! `Profiler` is a `Tracer`, so any machine can be profiled by running it with
+//! `Machine::run_traced`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use super::disassembler::format_instruction;
+use super::{Cell, Event, IntCode, Tracer};
+
+/// How many lines each section of the report lists.
+const REPORT_LINES: usize = 10;
+
+/// Counts how often every instruction runs, and every address is read and written.
+///
+/// It also samples a call stack, for flame graphs. Intcode has no calls, so the stack is
+/// guessed at: every taken jump starts a new frame, named after its target, and the frame an
+/// `ARB` with a positive offset runs in is pushed onto the stack until an `ARB` with a negative
+/// offset pops it again, which is how compiled Intcode allocates its stack frames.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    steps: u64,
+    executions: BTreeMap<usize, u64>,
+    /// How each address's instruction looked the first time it ran.
+    listing: BTreeMap<usize, String>,
+    opcodes: BTreeMap<&'static str, u64>,
+    reads: BTreeMap<usize, u64>,
+    writes: BTreeMap<usize, u64>,
+    stack: Vec<usize>,
+    frame: Option<usize>,
+    samples: BTreeMap<Vec<usize>, u64>,
+}
+
+/// The `n` largest counts, largest first, breaking ties by address.
+fn hottest<K: Copy + Ord>(counts: &BTreeMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut counts = counts
+        .iter()
+        .map(|(key, count)| (*key, *count))
+        .collect::<Vec<_>>();
+    counts.sort_by_key(|(key, count)| (std::cmp::Reverse(*count), *key));
+    counts.truncate(n);
+    counts
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// How many instructions have executed.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// How many times the instruction at `address` has executed.
+    pub fn executions(&self, address: usize) -> u64 {
+        self.executions.get(&address).cloned().unwrap_or(0)
+    }
+
+    /// How many times each kind of instruction has executed, by mnemonic.
+    pub fn opcodes(&self) -> &BTreeMap<&'static str, u64> {
+        &self.opcodes
+    }
+
+    /// The `n` most executed addresses, most executed first.
+    pub fn hottest_instructions(&self, n: usize) -> Vec<(usize, u64)> {
+        hottest(&self.executions, n)
+    }
+
+    /// The `n` addresses operands were most often read from, most read first.
+    pub fn hottest_reads(&self, n: usize) -> Vec<(usize, u64)> {
+        hottest(&self.reads, n)
+    }
+
+    /// The `n` most written addresses, most written first.
+    pub fn hottest_writes(&self, n: usize) -> Vec<(usize, u64)> {
+        hottest(&self.writes, n)
+    }
+
+    /// Writes the sampled stacks in the folded format read by `flamegraph.pl` and `inferno`:
+    /// one line per stack, with frames separated by semicolons and followed by the number of
+    /// instructions executed in it.
+    pub fn write_folded<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (stack, count) in &self.samples {
+            let frames = stack
+                .iter()
+                .map(|frame| format!("@{}", frame))
+                .collect::<Vec<_>>();
+            writeln!(out, "{} {}", frames.join(";"), count)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: Cell> Tracer<C> for Profiler {
+    fn after(&mut self, event: &Event<C>) {
+        self.steps += 1;
+        *self.executions.entry(event.ip).or_insert(0) += 1;
+        self.listing
+            .entry(event.ip)
+            .or_insert_with(|| format_instruction(&event.instruction, event.parameters));
+        *self
+            .opcodes
+            .entry(event.instruction.mnemonic())
+            .or_insert(0) += 1;
+        for address in event.reads {
+            *self.reads.entry(*address).or_insert(0) += 1;
+        }
+        for (address, _) in event.writes {
+            *self.writes.entry(*address).or_insert(0) += 1;
+        }
+
+        let frame = *self.frame.get_or_insert(event.ip);
+        let mut stack = self.stack.clone();
+        if stack.last() != Some(&frame) {
+            stack.push(frame);
+        }
+        *self.samples.entry(stack).or_insert(0) += 1;
+
+        if let IntCode::AdjustRelativeBase(_) = event.instruction {
+            let offset = &event.operands[0];
+            if *offset > C::zero() {
+                self.stack.push(frame);
+            } else if *offset < C::zero() {
+                self.stack.pop();
+            }
+        }
+        if event.next_ip != event.ip + event.instruction.instruction_width() {
+            self.frame = Some(event.next_ip);
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let share = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+
+        writeln!(f, "{} steps", self.steps)?;
+
+        writeln!(f, "\nHottest instructions:")?;
+        for (address, count) in self.hottest_instructions(REPORT_LINES) {
+            writeln!(
+                f,
+                "{:>12} {:>6.2}%  {:>6}  {}",
+                count,
+                share(count),
+                address,
+                self.listing[&address]
+            )?;
+        }
+
+        writeln!(f, "\nBy instruction:")?;
+        for (mnemonic, count) in hottest(&self.opcodes, self.opcodes.len()) {
+            writeln!(f, "{:>12} {:>6.2}%  {}", count, share(count), mnemonic)?;
+        }
+
+        writeln!(f, "\nMost read addresses:")?;
+        for (address, count) in self.hottest_reads(REPORT_LINES) {
+            writeln!(f, "{:>12}  [{}]", count, address)?;
+        }
+
+        writeln!(f, "\nMost written addresses:")?;
+        for (address, count) in self.hottest_writes(REPORT_LINES) {
+            writeln!(f, "{:>12}  [{}]", count, address)?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::assembler::assemble;
+    #[allow(unused_imports)]
+    use crate::intcode::{Machine, StepResult};
+
+    #[test]
+    fn it_counts_instructions_and_memory_accesses() {
+        // Adds 3 to [total] five times, then outputs it.
+        let program = assemble::<i64>(
+            "
+            loop:   ADD [total], #3 -> [total]
+                    ADD [count], #-1 -> [count]
+                    JT [count], #loop
+                    OUT [total]
+                    HLT
+            count:  .data 5
+            total:  .data 0
+            ",
+        )
+        .unwrap();
+        let mut machine = Machine::new(program);
+        let mut profiler = Profiler::new();
+
+        assert_eq!(
+            machine.run_traced(&mut profiler).unwrap(),
+            StepResult::Output(15)
+        );
+        assert_eq!(
+            machine.run_traced(&mut profiler).unwrap(),
+            StepResult::Halted
+        );
+
+        assert_eq!(profiler.steps(), 17);
+        assert_eq!(profiler.executions(0), 5);
+        assert_eq!(profiler.executions(11), 1);
+        assert_eq!(profiler.opcodes()["ADD"], 10);
+        assert_eq!(profiler.hottest_reads(2), vec![(14, 10), (15, 6)]);
+        assert_eq!(profiler.hottest_writes(2), vec![(14, 5), (15, 5)]);
+
+        let report = profiler.to_string();
+        assert!(report.starts_with("17 steps\n\nHottest instructions:\n"));
+        assert!(report.contains("           5  29.41%       0  ADD [15], #3 -> [15]\n"));
+        assert!(report.contains("          10  58.82%  ADD\n"));
+    }
+
+    #[test]
+    fn it_folds_stacks_at_relative_base_adjustments() {
+        let program = assemble::<i64>(
+            "
+                    ARB #1
+                    JT #1, #function
+            return: HLT
+            function:
+                    ARB #1
+                    ADD #1, #1 -> [x]
+                    ARB #-1
+                    JT #1, #return
+            x:      .data 0
+            ",
+        )
+        .unwrap();
+        let mut profiler = Profiler::new();
+        Machine::new(program).run_traced(&mut profiler).unwrap();
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "@0 2\n@0;@5 1\n@0;@6 4\n"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Save files holding the complete state of a paused machine, so it can be written to disk and
+//! resumed later.
+//!
+//! A save file is plain text. The first line names the format and its version, currently
+//! `intcode-save 1`, followed by one `key value` line for each register:
+//!
+//! * `ip` is the instruction pointer,
+//! * `relative-base` is the relative base,
+//! * `status` is one of `running`, `awaiting-input` or `halted`,
+//! * `steps` is how many instructions the machine has executed,
+//! * `input` is a comma separated list of the input values not yet consumed, possibly empty.
+//!
+//! Then `memory` followed by the number of cells in use, and finally the contents of memory as
+//! `address: value, value, ...` lines, each listing a run of consecutive non-zero cells starting
+//! at `address`. Cells that aren't listed are zero.
+//!
+//! Blank lines and lines starting with `#` are ignored, so a save file can be annotated by hand.
+//!
+//! ```text
+//! intcode-save 1
+//! ip 2
+//! relative-base 0
+//! status awaiting-input
+//! steps 1
+//! input
+//! memory 17
+//! 0: 3, 15, 3, 16, 1, 15, 16, 15, 102, 2, 15, 15, 4, 15, 99, 5
+//! ```
+//!
+//! Limits and the instructions leading up to the pause aren't saved.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write;
+
+use super::{Cell, Memory, Snapshot, Status};
+
+const MAGIC: &str = "intcode-save";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl LoadError {
+    fn new(line: usize, message: String) -> Self {
+        LoadError { line, message }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Running => "running",
+        Status::AwaitingInput => "awaiting-input",
+        Status::Halted => "halted",
+    }
+}
+
+fn join<'a, C: Cell + 'a>(values: impl IntoIterator<Item = &'a C>) -> String {
+    values
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Writes a snapshot out in the save file format.
+pub fn save<C: Cell>(snapshot: &Snapshot<C>) -> String {
+    let mut out = String::new();
+    writeln!(out, "{} {}", MAGIC, VERSION).unwrap();
+    writeln!(out, "ip {}", snapshot.ip).unwrap();
+    writeln!(out, "relative-base {}", snapshot.relative_base).unwrap();
+    writeln!(out, "status {}", status_name(snapshot.status)).unwrap();
+    writeln!(out, "steps {}", snapshot.steps).unwrap();
+    let input = format!("input {}", join(&snapshot.input));
+    writeln!(out, "{}", input.trim_end()).unwrap();
+    writeln!(out, "memory {}", snapshot.memory.len()).unwrap();
+
+    let mut run: Option<(usize, Vec<&C>)> = None;
+    for (address, value) in snapshot.memory.cells() {
+        if value.is_zero() {
+            continue;
+        }
+        match &mut run {
+            Some((start, values)) if *start + values.len() == address => values.push(value),
+            _ => {
+                if let Some((start, values)) = run.take() {
+                    writeln!(out, "{}: {}", start, join(values)).unwrap();
+                }
+                run = Some((address, vec![value]));
+            }
+        }
+    }
+    if let Some((start, values)) = run {
+        writeln!(out, "{}: {}", start, join(values)).unwrap();
+    }
+    out
+}
+
+struct Lines<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    line: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        for (index, line) in &mut self.lines {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                self.line = index + 1;
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    fn error(&self, message: String) -> LoadError {
+        LoadError::new(self.line, message)
+    }
+
+    fn field(&mut self, key: &str) -> Result<&'a str, LoadError> {
+        let line = match self.next() {
+            Some(line) => line,
+            None => {
+                return Err(self.error(format!("Expected `{}`, found the end of the file", key)))
+            }
+        };
+        let mut parts = line.splitn(2, ' ');
+        if parts.next() != Some(key) {
+            return Err(self.error(format!("Expected `{}`, found `{}`", key, line)));
+        }
+        Ok(parts.next().unwrap_or("").trim())
+    }
+
+    fn parse<T: std::str::FromStr>(&self, value: &str) -> Result<T, LoadError> {
+        value
+            .parse()
+            .map_err(|_| self.error(format!("`{}` is not a valid value here", value)))
+    }
+
+    fn parse_list<C: Cell>(&self, values: &str) -> Result<Vec<C>, LoadError> {
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+        values
+            .split(',')
+            .map(|value| self.parse(value.trim()))
+            .collect()
+    }
+}
+
+/// Reads a snapshot back from a save file.
+pub fn load<C: Cell>(save: &str) -> Result<Snapshot<C>, LoadError> {
+    let mut lines = Lines {
+        lines: save.lines().enumerate(),
+        line: 0,
+    };
+
+    let version = lines.field(MAGIC)?;
+    if lines.parse::<u32>(version)? != VERSION {
+        return Err(lines.error(format!("Unsupported save file version {}", version)));
+    }
+
+    let ip = lines.field("ip")?;
+    let ip = lines.parse(ip)?;
+    let relative_base = lines.field("relative-base")?;
+    let relative_base = lines.parse(relative_base)?;
+    let status = match lines.field("status")? {
+        "running" => Status::Running,
+        "awaiting-input" => Status::AwaitingInput,
+        "halted" => Status::Halted,
+        status => return Err(lines.error(format!("Unknown status `{}`", status))),
+    };
+    let steps = lines.field("steps")?;
+    let steps = lines.parse(steps)?;
+    let input = lines.field("input")?;
+    let input = lines
+        .parse_list(input)?
+        .into_iter()
+        .collect::<VecDeque<_>>();
+    let len = lines.field("memory")?;
+    let len: usize = lines.parse(len)?;
+
+    let mut memory = Memory::new(Vec::new());
+    while let Some(line) = lines.next() {
+        let mut parts = line.splitn(2, ':');
+        let start: usize = lines.parse(parts.next().unwrap().trim())?;
+        let values = parts
+            .next()
+            .ok_or_else(|| lines.error(format!("Expected `address: values`, found `{}`", line)))?;
+        let values = lines.parse_list::<C>(values.trim())?;
+        match start.checked_add(values.len()) {
+            Some(end) if end <= len => {}
+            _ => return Err(lines.error(format!("Cells beyond the end of memory at {}", len))),
+        }
+        for (offset, value) in values.into_iter().enumerate() {
+            memory.set(start + offset, value);
+        }
+    }
+    if memory.len() < len {
+        memory.set(len - 1, C::zero());
+    }
+
+    Ok(Snapshot {
+        memory,
+        ip,
+        relative_base,
+        status,
+        input,
+        recent: VecDeque::new(),
+        steps,
+    })
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::{Machine, StepResult};
+
+    #[test]
+    fn it_saves_and_resumes_a_paused_machine() {
+        let program = vec![3, 15, 3, 16, 1, 15, 16, 15, 102, 2, 15, 15, 4, 15, 99, 0, 0];
+        let mut machine = Machine::new(program);
+        machine.push_input(5);
+        assert_eq!(machine.run().unwrap(), StepResult::NeedsInput);
+        machine.memory_mut().set(5000, -7);
+
+        let saved = save(&machine.snapshot());
+        assert_eq!(
+            saved,
+            "intcode-save 1\n\
+             ip 2\n\
+             relative-base 0\n\
+             status awaiting-input\n\
+             steps 1\n\
+             input\n\
+             memory 5001\n\
+             0: 3, 15, 3, 16, 1, 15, 16, 15, 102, 2, 15, 15, 4, 15, 99, 5\n\
+             5000: -7\n"
+        );
+
+        let mut resumed = Machine::from(load::<i64>(&saved).unwrap());
+        assert_eq!(resumed.memory().len(), 5001);
+        resumed.push_input(1);
+        assert_eq!(resumed.run().unwrap(), StepResult::Output(12));
+        assert_eq!(resumed.steps(), 5);
+    }
+
+    #[test]
+    fn it_rejects_unknown_versions_and_malformed_lines() {
+        assert_eq!(
+            load::<i64>("# saved by hand\nintcode-save 2\n").unwrap_err(),
+            LoadError::new(2, "Unsupported save file version 2".to_owned())
+        );
+
+        let save = "intcode-save 1\nip 0\nrelative-base 0\nstatus running\nsteps 0\n\
+                    input 1, x\nmemory 1\n0: 99\n";
+        assert_eq!(
+            load::<i64>(save).unwrap_err(),
+            LoadError::new(6, "`x` is not a valid value here".to_owned())
+        );
+
+        let save = "intcode-save 1\nip 0\nrelative-base 0\nstatus running\nsteps 0\n\
+                    input\nmemory 1\n0: 99\n18446744073709551615: 1, 2\n";
+        assert_eq!(
+            load::<i64>(save).unwrap_err(),
+            LoadError::new(9, "Cells beyond the end of memory at 1".to_owned())
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use super::{Cell, IntCode, Machine, Memory, Status};
+
+/// Everything needed to put a `Machine` back the way it was at some earlier point.
+///
+/// Memory is shared with the machine the snapshot was taken from until one of them writes to
+/// it, a page at a time, so taking snapshots and restoring them is cheap even for large
+/// programs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<C: Cell = i64> {
+    pub(super) memory: Memory<C>,
+    pub(super) ip: usize,
+    pub(super) relative_base: C,
+    pub(super) status: Status,
+    pub(super) input: VecDeque<C>,
+    pub(super) recent: VecDeque<(usize, IntCode)>,
+    pub(super) steps: u64,
+}
+
+impl<C: Cell> Snapshot<C> {
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> &C {
+        &self.relative_base
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+impl<C: Cell> Machine<C> {
+    /// Captures the machine's memory, registers and pending input.
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base.clone(),
+            status: self.status,
+            input: self.input.clone(),
+            recent: self.recent.clone(),
+            steps: self.steps,
+        }
+    }
+
+    /// Puts the machine back into the state it was in when `snapshot` was taken. The machine's
+    /// limits are kept as they are.
+    ///
+    /// Decoded instructions are kept too, unless the snapshot holds something else at their
+    /// address, so a machine repeatedly restored to the same snapshot doesn't have to decode
+    /// its program from scratch every time.
+    pub fn restore(&mut self, snapshot: &Snapshot<C>) {
+        let decoded = &mut self.decoded;
+        self.memory
+            .differences(&snapshot.memory, decoded.len(), |index| {
+                decoded[index] = None
+            });
+        self.memory = snapshot.memory.clone();
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base.clone();
+        self.status = snapshot.status;
+        self.input = snapshot.input.clone();
+        self.recent = snapshot.recent.clone();
+        self.steps = snapshot.steps;
+        self.seen_states.clear();
+    }
+}
+
+impl<C: Cell> From<Snapshot<C>> for Machine<C> {
+    fn from(snapshot: Snapshot<C>) -> Self {
+        let mut machine = Machine::new(Vec::new());
+        machine.restore(&snapshot);
+        machine
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::StepResult;
+
+    #[test]
+    fn it_branches_from_a_mid_execution_state() {
+        // Outputs twice the sum of two inputs, then halts.
+        let program = vec![3, 15, 3, 16, 1, 15, 16, 15, 102, 2, 15, 15, 4, 15, 99, 0, 0];
+        let mut machine = Machine::new(program.clone());
+        machine.push_input(5);
+        assert_eq!(machine.run().unwrap(), StepResult::NeedsInput);
+        let halfway = machine.snapshot();
+
+        machine.push_input(1);
+        assert_eq!(machine.run().unwrap(), StepResult::Output(12));
+
+        machine.restore(&halfway);
+        assert_eq!(machine.ip(), 2);
+        machine.push_input(10);
+        assert_eq!(machine.run().unwrap(), StepResult::Output(30));
+
+        let mut branch = Machine::from(halfway.clone());
+        branch.push_input(-5);
+        assert_eq!(branch.run().unwrap(), StepResult::Output(0));
+        assert_eq!(halfway.memory().get(15), 5);
+    }
+}
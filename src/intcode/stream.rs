@@ -0,0 +1,185 @@
+//! An async adaptor for `Machine`, with input fed in through a `Sink` and output read from a
+//! `Stream`.
+//!
+//! The machine only runs while its output stream is being polled, and yields whenever it's
+//! waiting for input, so any number of machines can share a single-threaded executor.
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures::{Sink, Stream};
+
+use super::{Cell, ErrorKind, ExecutionError, Machine, StepResult};
+
+struct Shared<C: Cell> {
+    machine: Machine<C>,
+    input_closed: bool,
+    finished: bool,
+    /// The task waiting for input to arrive, if any.
+    waker: Option<Waker>,
+}
+
+impl<C: Cell> Shared<C> {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Feeds input to a machine. Sending never fails, even once the machine has halted.
+///
+/// The error type matches `MachineStream`'s so that one machine's output can be forwarded
+/// straight into another's input.
+pub struct MachineSink<C: Cell = i64> {
+    shared: Arc<Mutex<Shared<C>>>,
+}
+
+/// Everything a machine outputs, ending when it halts.
+///
+/// If the machine fails, or needs input after its sink has been closed, the stream yields the
+/// error and then ends.
+pub struct MachineStream<C: Cell = i64> {
+    shared: Arc<Mutex<Shared<C>>>,
+}
+
+/// Splits a machine into its input and output halves.
+pub fn split<C: Cell>(machine: Machine<C>) -> (MachineSink<C>, MachineStream<C>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        machine,
+        input_closed: false,
+        finished: false,
+        waker: None,
+    }));
+    (
+        MachineSink {
+            shared: shared.clone(),
+        },
+        MachineStream { shared },
+    )
+}
+
+impl<C: Cell> Sink<C> for MachineSink<C> {
+    type Error = ExecutionError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: C) -> Result<(), Self::Error> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.machine.push_input(item);
+        shared.wake();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.input_closed = true;
+        shared.wake();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C: Cell> Stream for MachineStream<C> {
+    type Item = Result<C, ExecutionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.finished {
+            return Poll::Ready(None);
+        }
+
+        match shared.machine.run() {
+            Ok(StepResult::Output(value)) => Poll::Ready(Some(Ok(value))),
+            Ok(StepResult::Halted) => {
+                shared.finished = true;
+                Poll::Ready(None)
+            }
+            Ok(StepResult::NeedsInput) if shared.input_closed => {
+                shared.finished = true;
+                let machine = &shared.machine;
+                let err = machine.error(machine.ip, ErrorKind::MissingInput.into());
+                Poll::Ready(Some(Err(err)))
+            }
+            Ok(StepResult::NeedsInput) => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(err) => {
+                shared.finished = true;
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use futures::executor::{block_on, LocalPool};
+    #[allow(unused_imports)]
+    use futures::task::LocalSpawnExt;
+    #[allow(unused_imports)]
+    use futures::{SinkExt, StreamExt, TryStreamExt};
+    #[allow(unused_imports)]
+    use std::cell::RefCell;
+    #[allow(unused_imports)]
+    use std::rc::Rc;
+
+    #[test]
+    fn a_ring_of_machines_runs_on_one_thread() {
+        // Adds one to each of three inputs, outputting each result.
+        let program = vec![
+            3, 20, 1001, 20, 1, 20, 4, 20, 1001, 21, -1, 21, 1005, 21, 0, 99, 0, 0, 0, 0, 0, 3,
+        ];
+        let (mut sinks, streams): (Vec<_>, Vec<_>) = (0..3)
+            .map(|index| {
+                let mut machine = Machine::new(program.clone());
+                if index == 0 {
+                    machine.push_input(0);
+                }
+                split(machine)
+            })
+            .unzip();
+        let mut pool = LocalPool::new();
+        let last = Rc::new(RefCell::new(Vec::new()));
+
+        // Each machine's output goes to the next one's input, and the last one's to the first.
+        sinks.rotate_left(1);
+        for (index, (stream, sink)) in streams.into_iter().zip(sinks).enumerate() {
+            let last = last.clone();
+            let stream = stream.inspect_ok(move |value| {
+                if index == 2 {
+                    last.borrow_mut().push(*value);
+                }
+            });
+            pool.spawner()
+                .spawn_local(async move { stream.forward(sink).await.unwrap() })
+                .unwrap();
+        }
+        pool.run();
+
+        assert_eq!(*last.borrow(), vec![3, 6, 9]);
+    }
+
+    #[test]
+    fn closing_the_input_of_a_waiting_machine_is_an_error() {
+        let (mut sink, mut stream) = split(Machine::new(vec![3, 7, 4, 7, 1105, 1, 0, 0]));
+
+        block_on(async {
+            sink.send(5).await.unwrap();
+            assert_eq!(stream.next().await.unwrap().unwrap(), 5);
+            sink.close().await.unwrap();
+            let err = stream.next().await.unwrap().unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::MissingInput);
+            assert!(stream.next().await.is_none());
+        });
+    }
+}
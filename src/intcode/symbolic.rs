@@ -0,0 +1,726 @@
+//! Symbolic execution of Intcode programs, for solving for the inputs that make a program
+//! produce a given value.
+//!
+//! Chosen memory cells start out as variables rather than numbers, and every instruction
+//! builds an expression out of its operands instead of computing a value. Control flow has to
+//! stay concrete: a jump on a condition that depends on a variable, a write to an address that
+//! does, or reading input, all end symbolic execution.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::{IntCode, Limits, Machine, OperationMode};
+
+/// How far past the end of memory a write or variable may be and still grow it. Anything further
+/// out stops symbolic execution rather than allocating every cell in between.
+const GROWTH_LIMIT: usize = 4096;
+
+/// A value computed from the initial contents of some memory cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    /// The initial value of the cell at an address.
+    Var(usize),
+    Add(Rc<Expr>, Rc<Expr>),
+    Multiply(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>),
+    /// A read from an address that depends on a variable, out of memory as it was at the time.
+    Load {
+        address: Rc<Expr>,
+        memory: Rc<Vec<Expr>>,
+    },
+}
+
+impl Expr {
+    fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Combines two expressions, folding them into a constant if they're both constants. Nothing
+    /// else is simplified away, so that evaluating the result fails wherever running this
+    /// operation would have.
+    fn binary(
+        a: Expr,
+        b: Expr,
+        fold: fn(i64, i64) -> Option<i64>,
+        build: fn(Rc<Expr>, Rc<Expr>) -> Expr,
+    ) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) if fold(x, y).is_some() => Expr::Const(fold(x, y).unwrap()),
+            _ => build(Rc::new(a), Rc::new(b)),
+        }
+    }
+
+    fn add(a: Expr, b: Expr) -> Expr {
+        Expr::binary(a, b, i64::checked_add, Expr::Add)
+    }
+
+    fn multiply(a: Expr, b: Expr) -> Expr {
+        Expr::binary(a, b, i64::checked_mul, Expr::Multiply)
+    }
+
+    fn less_than(a: Expr, b: Expr) -> Expr {
+        Expr::binary(a, b, |x, y| Some((x < y) as i64), Expr::LessThan)
+    }
+
+    fn equals(a: Expr, b: Expr) -> Expr {
+        Expr::binary(a, b, |x, y| Some((x == y) as i64), Expr::Equals)
+    }
+
+    /// Works out the expression's value given the values of its variables, or `None` if running
+    /// the program with those values would fail on an overflow or a negative address.
+    pub fn evaluate(&self, variables: &dyn Fn(usize) -> i64) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Var(address) => Some(variables(*address)),
+            Expr::Add(a, b) => a.evaluate(variables)?.checked_add(b.evaluate(variables)?),
+            Expr::Multiply(a, b) => a.evaluate(variables)?.checked_mul(b.evaluate(variables)?),
+            Expr::LessThan(a, b) => Some((a.evaluate(variables)? < b.evaluate(variables)?) as i64),
+            Expr::Equals(a, b) => Some((a.evaluate(variables)? == b.evaluate(variables)?) as i64),
+            Expr::Load { address, memory } => {
+                let address = usize::try_from(address.evaluate(variables)?).ok()?;
+                match memory.get(address) {
+                    Some(value) => value.evaluate(variables),
+                    None => Some(0),
+                }
+            }
+        }
+    }
+
+    /// The expression as a sum of variables times constants, if it is one.
+    pub fn linear(&self) -> Option<Linear> {
+        Some(match self {
+            Expr::Const(value) => Linear {
+                constant: *value,
+                coefficients: BTreeMap::new(),
+            },
+            Expr::Var(address) => Linear {
+                constant: 0,
+                coefficients: Some((*address, 1)).into_iter().collect(),
+            },
+            Expr::Add(a, b) => {
+                let (mut a, b) = (a.linear()?, b.linear()?);
+                a.constant = a.constant.checked_add(b.constant)?;
+                for (address, coefficient) in b.coefficients {
+                    let sum = a.coefficients.entry(address).or_insert(0);
+                    *sum = sum.checked_add(coefficient)?;
+                }
+                a
+            }
+            Expr::Multiply(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                let (factor, mut term) =
+                    match (a.coefficients.is_empty(), b.coefficients.is_empty()) {
+                        (true, _) => (a.constant, b),
+                        (_, true) => (b.constant, a),
+                        _ => return None,
+                    };
+                term.constant = term.constant.checked_mul(factor)?;
+                for coefficient in term.coefficients.values_mut() {
+                    *coefficient = coefficient.checked_mul(factor)?;
+                }
+                term
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Var(address) => write!(f, "[{}]", address),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load { address, .. } => write!(f, "load({})", address),
+        }
+    }
+}
+
+/// A constant plus a sum of variables each multiplied by a constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i64,
+    /// Coefficients by variable address. Variables that cancel out are left in with a
+    /// coefficient of zero.
+    pub coefficients: BTreeMap<usize, i64>,
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.constant)?;
+        for (address, coefficient) in &self.coefficients {
+            match coefficient {
+                1 => write!(f, " + [{}]", address)?,
+                _ => write!(f, " + {} * [{}]", coefficient, address)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why symbolic execution had to stop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsupported {
+    /// The instruction itself depends on a variable.
+    SymbolicInstruction {
+        ip: usize,
+    },
+    SymbolicJump {
+        ip: usize,
+    },
+    SymbolicWrite {
+        ip: usize,
+    },
+    SymbolicRelativeBase {
+        ip: usize,
+    },
+    Input {
+        ip: usize,
+    },
+    /// Running the program would fail no matter what the variables were.
+    Fault {
+        ip: usize,
+    },
+    /// A write or variable too far past the end of memory to keep track of.
+    DistantAddress {
+        address: usize,
+    },
+    StepLimitExceeded,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unsupported::SymbolicInstruction { ip } => {
+                write!(f, "The instruction at {} depends on a variable", ip)
+            }
+            Unsupported::SymbolicJump { ip } => {
+                write!(
+                    f,
+                    "Whether the jump at {} is taken depends on a variable",
+                    ip
+                )
+            }
+            Unsupported::SymbolicWrite { ip } => {
+                write!(
+                    f,
+                    "Where the instruction at {} writes depends on a variable",
+                    ip
+                )
+            }
+            Unsupported::SymbolicRelativeBase { ip } => write!(
+                f,
+                "The relative base adjustment at {} depends on a variable",
+                ip
+            ),
+            Unsupported::Input { ip } => write!(f, "The instruction at {} reads input", ip),
+            Unsupported::Fault { ip } => write!(f, "The instruction at {} always fails", ip),
+            Unsupported::DistantAddress { address } => write!(
+                f,
+                "Address {} is too far past the end of memory to run symbolically",
+                address
+            ),
+            Unsupported::StepLimitExceeded => {
+                write!(f, "Program didn't halt within the step limit")
+            }
+        }
+    }
+}
+
+/// The final state of a program run symbolically to completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub memory: Vec<Expr>,
+    pub outputs: Vec<Expr>,
+    /// Every intermediate result that could fail to evaluate. Running the program for real only
+    /// succeeds for values of the variables that all of these evaluate for.
+    pub conditions: Vec<Expr>,
+}
+
+impl Outcome {
+    /// Whether running the program with the given values for its variables would succeed.
+    pub fn succeeds(&self, variables: &dyn Fn(usize) -> i64) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.evaluate(variables).is_some())
+    }
+}
+
+struct Executor {
+    /// Shared with the `Load` expressions that read from it, and only copied when written to
+    /// while they still do.
+    memory: Rc<Vec<Expr>>,
+    ip: usize,
+    relative_base: i64,
+    conditions: Vec<Expr>,
+}
+
+/// Grows `memory` to take in `address`, unless it's too far out.
+fn reach(memory: &mut Rc<Vec<Expr>>, address: usize) -> Result<(), Unsupported> {
+    if address < memory.len() {
+        return Ok(());
+    }
+    if address - memory.len() >= GROWTH_LIMIT {
+        return Err(Unsupported::DistantAddress { address });
+    }
+    Rc::make_mut(memory).resize(address + 1, Expr::Const(0));
+    Ok(())
+}
+
+impl Executor {
+    /// Notes an intermediate result that can fail to evaluate, unless it's already been
+    /// computed.
+    fn checked(&mut self, value: Expr) -> Expr {
+        if value.as_const().is_none() {
+            self.conditions.push(value.clone());
+        }
+        value
+    }
+
+    fn get(&self, index: usize) -> Expr {
+        self.memory.get(index).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    fn parameter(&self, offset: usize) -> Expr {
+        self.get(self.ip + offset)
+    }
+
+    /// Resolves a position or relative mode parameter to an address, if it doesn't depend on a
+    /// variable.
+    fn address(&self, offset: usize, mode: OperationMode) -> Result<Option<usize>, Unsupported> {
+        let base = match mode {
+            OperationMode::Position => 0,
+            OperationMode::Relative => self.relative_base,
+            OperationMode::Immediate => unreachable!("immediate mode parameters have no address"),
+        };
+        match self.parameter(offset).as_const() {
+            Some(parameter) => {
+                let address = base
+                    .checked_add(parameter)
+                    .ok_or(Unsupported::Fault { ip: self.ip })?;
+                let address =
+                    usize::try_from(address).map_err(|_| Unsupported::Fault { ip: self.ip })?;
+                Ok(Some(address))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn read(&mut self, offset: usize, mode: OperationMode) -> Result<Expr, Unsupported> {
+        if mode == OperationMode::Immediate {
+            return Ok(self.parameter(offset));
+        }
+        let address = match self.address(offset, mode)? {
+            Some(address) => return Ok(self.get(address)),
+            None if mode == OperationMode::Relative => {
+                Expr::add(Expr::Const(self.relative_base), self.parameter(offset))
+            }
+            None => self.parameter(offset),
+        };
+        let load = Expr::Load {
+            address: Rc::new(address),
+            memory: self.memory.clone(),
+        };
+        Ok(self.checked(load))
+    }
+
+    fn write(
+        &mut self,
+        offset: usize,
+        mode: OperationMode,
+        value: Expr,
+    ) -> Result<(), Unsupported> {
+        let address = self
+            .address(offset, mode)?
+            .ok_or(Unsupported::SymbolicWrite { ip: self.ip })?;
+        reach(&mut self.memory, address)?;
+        Rc::make_mut(&mut self.memory)[address] = value;
+        Ok(())
+    }
+
+    fn condition(&self, value: Expr) -> Result<bool, Unsupported> {
+        match value.as_const() {
+            Some(value) => Ok(value != 0),
+            None => Err(Unsupported::SymbolicJump { ip: self.ip }),
+        }
+    }
+
+    fn jump(&mut self, offset: usize, mode: OperationMode) -> Result<(), Unsupported> {
+        let target = self.read(offset, mode)?;
+        let target = target
+            .as_const()
+            .ok_or(Unsupported::SymbolicJump { ip: self.ip })?;
+        self.ip = usize::try_from(target).map_err(|_| Unsupported::Fault { ip: self.ip })?;
+        Ok(())
+    }
+}
+
+/// Runs `program` with the cells at `variables` left symbolic, until it halts.
+pub fn execute_symbolic(
+    program: &[i64],
+    variables: &[usize],
+    limits: Limits,
+) -> Result<Outcome, Unsupported> {
+    let mut executor = Executor {
+        memory: Rc::new(program.iter().cloned().map(Expr::Const).collect()),
+        ip: 0,
+        relative_base: 0,
+        conditions: Vec::new(),
+    };
+    for &address in variables {
+        reach(&mut executor.memory, address)?;
+        Rc::make_mut(&mut executor.memory)[address] = Expr::Var(address);
+    }
+    let mut outputs = Vec::new();
+    let mut steps = 0;
+
+    loop {
+        if limits.step_limit.is_some_and(|limit| steps >= limit) {
+            return Err(Unsupported::StepLimitExceeded);
+        }
+        steps += 1;
+
+        let ip = executor.ip;
+        let word = executor
+            .get(ip)
+            .as_const()
+            .ok_or(Unsupported::SymbolicInstruction { ip })?;
+        let instruction = i32::try_from(word)
+            .ok()
+            .and_then(|word| IntCode::try_from(word).ok())
+            .ok_or(Unsupported::Fault { ip })?;
+        let next = ip + instruction.instruction_width();
+
+        match instruction {
+            IntCode::Add(a, b, c) => {
+                let value = Expr::add(executor.read(1, a)?, executor.read(2, b)?);
+                let value = executor.checked(value);
+                executor.write(3, c, value)?;
+            }
+            IntCode::Multiply(a, b, c) => {
+                let value = Expr::multiply(executor.read(1, a)?, executor.read(2, b)?);
+                let value = executor.checked(value);
+                executor.write(3, c, value)?;
+            }
+            IntCode::LessThan(a, b, c) => {
+                let value = Expr::less_than(executor.read(1, a)?, executor.read(2, b)?);
+                executor.write(3, c, value)?;
+            }
+            IntCode::Equals(a, b, c) => {
+                let value = Expr::equals(executor.read(1, a)?, executor.read(2, b)?);
+                executor.write(3, c, value)?;
+            }
+            IntCode::StoreInput(_) => return Err(Unsupported::Input { ip }),
+            IntCode::LoadOutput(a) => outputs.push(executor.read(1, a)?),
+            IntCode::JumpIfTrue(a, b) => {
+                let value = executor.read(1, a)?;
+                if executor.condition(value)? {
+                    executor.jump(2, b)?;
+                    continue;
+                }
+            }
+            IntCode::JumpIfFalse(a, b) => {
+                let value = executor.read(1, a)?;
+                if !executor.condition(value)? {
+                    executor.jump(2, b)?;
+                    continue;
+                }
+            }
+            IntCode::AdjustRelativeBase(a) => {
+                let offset = executor
+                    .read(1, a)?
+                    .as_const()
+                    .ok_or(Unsupported::SymbolicRelativeBase { ip })?;
+                executor.relative_base = executor
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(Unsupported::Fault { ip })?;
+            }
+            IntCode::Halt => {
+                return Ok(Outcome {
+                    memory: Rc::try_unwrap(executor.memory).unwrap_or_else(|rc| (*rc).clone()),
+                    outputs,
+                    conditions: executor.conditions,
+                })
+            }
+        }
+        executor.ip = next;
+    }
+}
+
+/// A memory cell to solve for, along with the values it may take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub address: usize,
+    pub domain: Range<i64>,
+}
+
+/// Calls `f` with every combination of values for `variables`, in order, until it returns
+/// something.
+fn enumerate<T>(variables: &[Variable], f: &mut dyn FnMut(&[i64]) -> Option<T>) -> Option<T> {
+    fn go<T>(
+        variables: &[Variable],
+        values: &mut Vec<i64>,
+        f: &mut dyn FnMut(&[i64]) -> Option<T>,
+    ) -> Option<T> {
+        match variables.split_first() {
+            None => f(values),
+            Some((variable, rest)) => {
+                for value in variable.domain.clone() {
+                    values.push(value);
+                    let found = go(rest, values, f);
+                    values.pop();
+                    if found.is_some() {
+                        return found;
+                    }
+                }
+                None
+            }
+        }
+    }
+    go(variables, &mut Vec::new(), f)
+}
+
+/// Finds values for `variables` that leave `target` in the cell at `address` once the program
+/// halts. If several do, the first in the order the variables are given wins, as if every
+/// combination had been tried in turn.
+///
+/// If the cell's final value is a linear function of the variables, the last variable it
+/// depends on is solved for directly. Otherwise every combination is tried, by evaluating the
+/// cell's expression if the program could be run symbolically, and by actually running it if
+/// not. Either way, values are only accepted if the whole program would run with them, not just
+/// the part of it that computes the cell.
+pub fn solve(
+    program: &[i64],
+    variables: &[Variable],
+    address: usize,
+    target: i64,
+    limits: Limits,
+) -> Option<Vec<i64>> {
+    let addresses = variables.iter().map(|v| v.address).collect::<Vec<_>>();
+    let outcome = match execute_symbolic(program, &addresses, limits) {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            return enumerate(variables, &mut |values| {
+                let mut machine = Machine::new(program.to_vec()).with_limits(limits);
+                for (variable, value) in variables.iter().zip(values) {
+                    machine.set_memory(variable.address, *value);
+                }
+                let halted = machine.run_to_completion().is_ok();
+                if halted && machine.memory().get(address) == target {
+                    Some(values.to_vec())
+                } else {
+                    None
+                }
+            })
+        }
+    };
+
+    let expr = outcome
+        .memory
+        .get(address)
+        .cloned()
+        .unwrap_or(Expr::Const(0));
+    let check = |values: &[i64]| {
+        let value_of = |address| {
+            let index = addresses.iter().position(|a| *a == address).unwrap();
+            values[index]
+        };
+        if expr.evaluate(&value_of) == Some(target) && outcome.succeeds(&value_of) {
+            Some(values.to_vec())
+        } else {
+            None
+        }
+    };
+
+    let linear = expr.linear();
+    let solved = linear.as_ref().and_then(|linear| {
+        variables
+            .iter()
+            .rposition(|v| linear.coefficients.get(&v.address).is_some_and(|c| *c != 0))
+    });
+    let (linear, solved) = match (linear, solved) {
+        (Some(linear), Some(solved)) => (linear, solved),
+        _ => return enumerate(variables, &mut |values| check(values)),
+    };
+
+    let (before, after) = variables.split_at(solved);
+    let (variable, after) = after.split_first().unwrap();
+    let coefficient = linear.coefficients[&variable.address];
+    enumerate(before, &mut |first| {
+        // The variables after the one being solved for don't affect the result, so the first
+        // value of each will do.
+        let rest = after
+            .iter()
+            .map(|v| v.domain.clone().next())
+            .collect::<Option<Vec<_>>>()?;
+        let mut remainder = target.checked_sub(linear.constant)?;
+        for (v, value) in before.iter().zip(first) {
+            let term = linear.coefficients.get(&v.address).unwrap_or(&0);
+            remainder = remainder.checked_sub(term.checked_mul(*value)?)?;
+        }
+        if remainder.checked_rem(coefficient)? != 0 {
+            return None;
+        }
+        let value = remainder.checked_div(coefficient)?;
+        if !variable.domain.contains(&value) {
+            return None;
+        }
+        let values = first
+            .iter()
+            .cloned()
+            .chain(Some(value))
+            .chain(rest)
+            .collect::<Vec<_>>();
+        check(&values)
+    })
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[allow(dead_code)]
+    fn day2_variables() -> Vec<Variable> {
+        vec![
+            Variable {
+                address: 1,
+                domain: 0..100,
+            },
+            Variable {
+                address: 2,
+                domain: 0..100,
+            },
+        ]
+    }
+
+    #[test]
+    fn it_builds_linear_expressions() {
+        // [0] = ([1] + [2]) * 3 + 4, after a first instruction that uses them as addresses.
+        let program = vec![1, 0, 0, 0, 1, 1, 2, 0, 1002, 0, 3, 0, 1001, 0, 4, 0, 99];
+        let outcome = execute_symbolic(&program, &[1, 2], Limits::default()).unwrap();
+
+        let linear = outcome.memory[0].linear().unwrap();
+        assert_eq!(linear.to_string(), "4 + 3 * [1] + 3 * [2]");
+        assert_eq!(
+            solve(&program, &day2_variables(), 0, 100, Limits::default()),
+            Some(vec![0, 32])
+        );
+        assert_eq!(
+            solve(&program, &day2_variables(), 0, 101, Limits::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn it_enumerates_non_linear_expressions() {
+        // [0] = [1] * [2]
+        let program = vec![1102, 0, 0, 0, 99];
+        let outcome = execute_symbolic(&program, &[1, 2], Limits::default()).unwrap();
+
+        assert_eq!(outcome.memory[0].linear(), None);
+        assert_eq!(
+            solve(&program, &day2_variables(), 0, 91, Limits::default()),
+            Some(vec![1, 91])
+        );
+    }
+
+    #[test]
+    fn it_finds_no_solution_when_solving_overflows() {
+        // [0] = -[5], which can't be i64::MIN.
+        let program = vec![1002, 5, -1, 0, 99, 0];
+        let variables = vec![Variable {
+            address: 5,
+            domain: i64::MIN..i64::MAX,
+        }];
+
+        assert_eq!(
+            solve(&program, &variables, 0, i64::MIN, Limits::default()),
+            None
+        );
+        assert_eq!(
+            solve(&program, &variables, 0, -5, Limits::default()),
+            Some(vec![5])
+        );
+    }
+
+    #[test]
+    fn it_rejects_values_that_fail_elsewhere_in_the_program() {
+        // [0] = [9] + [10] is fine, but [11] = [9] * [10] overflows.
+        let program = vec![2, 9, 10, 11, 1, 9, 10, 0, 99, 0, 0, 0];
+        let variables = vec![
+            Variable {
+                address: 9,
+                domain: 4_000_000_000..4_000_000_001,
+            },
+            Variable {
+                address: 10,
+                domain: 4_000_000_000..4_000_000_001,
+            },
+        ];
+
+        assert_eq!(
+            solve(&program, &variables, 0, 8_000_000_000, Limits::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn it_stops_at_distant_writes() {
+        assert_eq!(
+            execute_symbolic(&[1101, 1, 1, 1_000_000_000_000, 99], &[], Limits::default()),
+            Err(Unsupported::DistantAddress {
+                address: 1_000_000_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn it_solves_day2_without_enumerating() {
+        let program = crate::day2::generate_input(include_str!("../../input/2019/day2.txt").trim());
+        let outcome = execute_symbolic(&program, &[1, 2], Limits::default()).unwrap();
+
+        let linear = outcome.memory[0].linear().unwrap();
+        assert_eq!(linear.coefficients.values().filter(|c| **c != 0).count(), 2);
+        assert_eq!(
+            solve(
+                &program,
+                &day2_variables(),
+                0,
+                19_690_720,
+                Limits::default()
+            ),
+            Some(vec![79, 60])
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_running_the_program() {
+        // Halts straight away unless [1] is 7, and otherwise sets [0] to 5.
+        let program = vec![1108, 0, 7, 13, 1005, 13, 8, 99, 1101, 2, 3, 0, 99, 0];
+        assert_eq!(
+            execute_symbolic(&program, &[1], Limits::default()),
+            Err(Unsupported::SymbolicJump { ip: 4 })
+        );
+
+        let variables = vec![Variable {
+            address: 1,
+            domain: 0..10,
+        }];
+        assert_eq!(
+            solve(&program, &variables, 0, 5, Limits::default()),
+            Some(vec![7])
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::disassembler::format_instruction;
+use super::{Cell, IntCode};
+
+/// Everything one instruction did, as reported to `Tracer::after`.
+#[derive(Debug)]
+pub struct Event<'a, C: Cell = i64> {
+    pub ip: usize,
+    pub instruction: IntCode,
+    /// The raw words following the opcode, as they were before the instruction ran.
+    pub parameters: &'a [C],
+    /// The values the instruction read, with every parameter's mode already applied.
+    pub operands: &'a [C],
+    /// The addresses of the operands that weren't immediate, in the same order.
+    pub reads: &'a [usize],
+    /// Every address the instruction wrote to, along with the value written.
+    pub writes: &'a [(usize, C)],
+    /// Where execution continues from after the instruction.
+    pub next_ip: usize,
+}
+
+impl<'a, C: Cell> fmt::Display for Event<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6}  {:<32}",
+            self.ip,
+            format_instruction(&self.instruction, self.parameters)
+        )?;
+        if !self.operands.is_empty() {
+            let operands = self
+                .operands
+                .iter()
+                .map(|operand| operand.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "  read {}", operands.join(", "))?;
+        }
+        for (address, value) in self.writes {
+            write!(f, "  [{}] <- {}", address, value)?;
+        }
+        if self.next_ip != self.ip + self.instruction.instruction_width() {
+            write!(f, "  jump {}", self.next_ip)?;
+        }
+        Ok(())
+    }
+}
+
+/// Observes a `Machine` as it executes, one instruction at a time.
+///
+/// Machines run with `NoTracer` unless told otherwise, and since that sets `ENABLED` to false
+/// the bookkeeping needed to build `Event`s is compiled out entirely.
+pub trait Tracer<C: Cell = i64> {
+    /// Whether the machine should collect operands and writes for `after`.
+    const ENABLED: bool = true;
+
+    /// Called once an instruction has been decoded, before it executes.
+    fn before(&mut self, _ip: usize, _instruction: &IntCode) {}
+
+    /// Called after an instruction has executed successfully.
+    fn after(&mut self, _event: &Event<C>) {}
+}
+
+/// A tracer that does nothing, at no cost.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoTracer;
+
+impl<C: Cell> Tracer<C> for NoTracer {
+    const ENABLED: bool = false;
+}
+
+/// Prints every executed instruction to stderr.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StderrTracer;
+
+impl<C: Cell> Tracer<C> for StderrTracer {
+    fn after(&mut self, event: &Event<C>) {
+        eprintln!("{}", event);
+    }
+}
+
+/// An owned copy of an `Event`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<C: Cell = i64> {
+    pub ip: usize,
+    pub instruction: IntCode,
+    pub parameters: Vec<C>,
+    pub operands: Vec<C>,
+    pub reads: Vec<usize>,
+    pub writes: Vec<(usize, C)>,
+    pub next_ip: usize,
+}
+
+impl<C: Cell> Record<C> {
+    pub fn as_event(&self) -> Event<'_, C> {
+        Event {
+            ip: self.ip,
+            instruction: self.instruction,
+            parameters: &self.parameters,
+            operands: &self.operands,
+            reads: &self.reads,
+            writes: &self.writes,
+            next_ip: self.next_ip,
+        }
+    }
+}
+
+impl<'a, C: Cell> From<&Event<'a, C>> for Record<C> {
+    fn from(event: &Event<'a, C>) -> Self {
+        Record {
+            ip: event.ip,
+            instruction: event.instruction,
+            parameters: event.parameters.to_vec(),
+            operands: event.operands.to_vec(),
+            reads: event.reads.to_vec(),
+            writes: event.writes.to_vec(),
+            next_ip: event.next_ip,
+        }
+    }
+}
+
+/// Remembers the last `capacity` instructions executed.
+#[derive(Debug, Clone)]
+pub struct RingBufferTracer<C: Cell = i64> {
+    capacity: usize,
+    records: VecDeque<Record<C>>,
+}
+
+impl<C: Cell> RingBufferTracer<C> {
+    pub fn new(capacity: usize) -> Self {
+        RingBufferTracer {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The recorded instructions, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &Record<C>> {
+        self.records.iter()
+    }
+}
+
+impl<C: Cell> Tracer<C> for RingBufferTracer<C> {
+    fn after(&mut self, event: &Event<C>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(Record::from(event));
+    }
+}
+
+impl<C: Cell> fmt::Display for RingBufferTracer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for record in &self.records {
+            writeln!(f, "{}", record.as_event())?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::intcode::{Machine, OperationMode, StepResult};
+
+    #[test]
+    fn ring_buffer_keeps_the_most_recent_instructions() {
+        let mut machine = Machine::new(vec![3, 11, 1001, 11, 5, 11, 1005, 11, 10, 99, 104, 0]);
+        machine.push_input(2);
+        let mut tracer = RingBufferTracer::new(3);
+
+        assert_eq!(
+            machine.run_traced(&mut tracer).unwrap(),
+            StepResult::Output(7)
+        );
+
+        let records = tracer.records().collect::<Vec<_>>();
+        assert_eq!(
+            *records[0],
+            Record {
+                ip: 2,
+                instruction: IntCode::Add(
+                    OperationMode::Position,
+                    OperationMode::Immediate,
+                    OperationMode::Position
+                ),
+                parameters: vec![11, 5, 11],
+                operands: vec![2, 5],
+                reads: vec![11],
+                writes: vec![(11, 7)],
+                next_ip: 6,
+            }
+        );
+        assert_eq!(records[1].next_ip, 10);
+        assert_eq!(records[2].operands, vec![7]);
+        assert_eq!(
+            tracer.to_string(),
+            "     2  ADD [11], #5 -> [11]              read 2, 5  [11] <- 7\n     \
+                  6  JT [11], #10                      read 7, 10  jump 10\n    \
+                 10  OUT #7                            read 7\n"
+        );
+    }
+
+    #[test]
+    fn tracer_sees_every_instruction_before_it_runs() {
+        #[derive(Default)]
+        struct Addresses(Vec<usize>);
+
+        impl Tracer for Addresses {
+            const ENABLED: bool = false;
+
+            fn before(&mut self, ip: usize, _instruction: &IntCode) {
+                self.0.push(ip);
+            }
+        }
+
+        let mut machine = Machine::new(vec![1101, 1, 1, 5, 104, 0, 99]);
+        let mut tracer = Addresses::default();
+
+        machine.run_traced(&mut tracer).unwrap();
+        machine.run_traced(&mut tracer).unwrap();
+
+        assert_eq!(tracer.0, vec![0, 4, 6]);
+    }
+}
@@ -0,0 +1,91 @@
+use std::convert::TryFrom;
+
+use advent_of_code_2019::intcode::{DecodeError, IntCode};
+use proptest::prelude::*;
+
+/// Opcodes alongside whether each of their parameters is written to.
+const SIGNATURES: &[(i32, &[bool])] = &[
+    (1, &[false, false, true]),
+    (2, &[false, false, true]),
+    (3, &[true]),
+    (4, &[false]),
+    (5, &[false, false]),
+    (6, &[false, false]),
+    (7, &[false, false, true]),
+    (8, &[false, false, true]),
+    (9, &[false]),
+    (99, &[]),
+];
+
+fn valid_encoding() -> impl Strategy<Value = i32> {
+    proptest::sample::select(SIGNATURES).prop_flat_map(|(opcode, writes)| {
+        let modes = writes
+            .iter()
+            .map(|&write| {
+                if write {
+                    proptest::sample::select(vec![0, 2]).boxed()
+                } else {
+                    (0..3).boxed()
+                }
+            })
+            .collect::<Vec<_>>();
+        modes.prop_map(move |modes| {
+            modes
+                .iter()
+                .rev()
+                .fold(0, |digits, mode| digits * 10 + mode)
+                * 100
+                + opcode
+        })
+    })
+}
+
+#[test]
+fn decodes_exactly_the_valid_encodings() {
+    let expected = SIGNATURES
+        .iter()
+        .map(|(_, writes)| {
+            writes
+                .iter()
+                .map(|&write| if write { 2 } else { 3 })
+                .product::<usize>()
+        })
+        .sum::<usize>();
+
+    let decoded = (0..100_000)
+        .filter(|word| IntCode::try_from(*word).is_ok())
+        .count();
+
+    assert_eq!(decoded, expected);
+}
+
+proptest! {
+    #[test]
+    fn valid_encodings_round_trip(word in valid_encoding()) {
+        let instruction = IntCode::try_from(word).unwrap();
+
+        prop_assert_eq!(instruction.encode(), word);
+        prop_assert_eq!(instruction.instruction_width(), instruction.modes().len() + 1);
+    }
+
+    #[test]
+    fn decoding_never_panics(word in any::<i32>()) {
+        if let Ok(instruction) = IntCode::try_from(word) {
+            prop_assert_eq!(instruction.encode(), word);
+        }
+    }
+
+    #[test]
+    fn invalid_mode_digits_are_reported(word in valid_encoding(), digit in 3..10, parameter in 1..4u32) {
+        let instruction = IntCode::try_from(word).unwrap();
+        prop_assume!(parameter as usize <= instruction.modes().len());
+
+        let place = 10_i32.pow(parameter + 1);
+        let corrupted = word - (word / place % 10) * place + digit * place;
+
+        prop_assert_eq!(
+            IntCode::try_from(corrupted),
+            Err(DecodeError::InvalidMode { word: corrupted, parameter: parameter as usize, digit })
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use advent_of_code_2019::intcode::{self, jit};
+use proptest::prelude::*;
+
+fn parse(program: &str) -> Vec<i64> {
+    program
+        .trim()
+        .split(',')
+        .map(|n| n.parse().unwrap())
+        .collect()
+}
+
+/// Runs `program` through both the interpreter and the compiler, and checks that they agree on
+/// the output, the final memory and, if it fails, how.
+fn assert_same(program: &[i64], inputs: &[i64]) {
+    let mut interpreted = program.to_vec();
+    let mut compiled = program.to_vec();
+
+    let expected = intcode::execute(&mut interpreted, inputs);
+    let actual = jit::execute(&mut compiled, inputs);
+
+    assert_eq!(actual, expected);
+    assert_eq!(compiled, interpreted);
+}
+
+#[test]
+fn day2_examples() {
+    for program in &[
+        "1,9,10,3,2,3,11,0,99,30,40,50",
+        "1,0,0,0,99",
+        "2,3,0,3,99",
+        "2,4,4,5,99,0",
+        "1,1,1,4,99,5,6,0,99",
+    ] {
+        assert_same(&parse(program), &[]);
+    }
+}
+
+#[test]
+fn day2_nouns_and_verbs() {
+    let mut program = parse(include_str!("../input/2019/day2.txt"));
+    for noun in (0..100).step_by(9).chain(Some(79)) {
+        for verb in (0..100).step_by(9).chain(Some(60)) {
+            program[1] = noun;
+            program[2] = verb;
+            assert_same(&program, &[]);
+        }
+    }
+}
+
+#[test]
+fn code_at_distant_addresses() {
+    assert_same(
+        &[1101, 99, 0, 1_000_000_000_000, 1105, 1, 1_000_000_000_000],
+        &[],
+    );
+
+    let mut machine = jit::JitMachine::new(vec![
+        1101_i64,
+        99,
+        0,
+        1_000_000_000_000,
+        1105,
+        1,
+        1_000_000_000_000,
+    ]);
+    assert_eq!(machine.run().unwrap(), intcode::StepResult::Halted);
+}
+
+#[test]
+fn errors_list_the_same_recent_instructions() {
+    // Counts [20] down from 3 in compiled code, then overflows multiplying.
+    assert_same(
+        &parse("1101,3,0,20,1001,20,-1,20,1005,20,4,1102,9223372036854775807,2,21,99"),
+        &[],
+    );
+}
+
+#[test]
+fn day5_examples() {
+    for program in &[
+        "3,9,8,9,10,9,4,9,99,-1,8",
+        "3,9,7,9,10,9,4,9,99,-1,8",
+        "3,3,1108,-1,8,3,4,3,99",
+        "3,3,1107,-1,8,3,4,3,99",
+        "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+        "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+        "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,\
+         20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99",
+    ] {
+        for input in -2..12 {
+            assert_same(&parse(program), &[input]);
+        }
+    }
+}
+
+#[test]
+fn day5_diagnostics() {
+    let program = parse(include_str!("../input/2019/day5.txt"));
+    for input in 0..10 {
+        assert_same(&program, &[input]);
+    }
+}
+
+fn instruction() -> impl Strategy<Value = Vec<i64>> {
+    let opcode = proptest::sample::select(vec![1, 2, 4, 5, 6, 7, 8, 9, 99]);
+    let modes = proptest::collection::vec(0..3i64, 3);
+    let parameters = proptest::collection::vec(-2..40i64, 3);
+    (opcode, modes, parameters).prop_map(|(opcode, mut modes, parameters)| {
+        let width = match opcode {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            4 | 9 => 2,
+            _ => 1,
+        };
+        if width == 4 && modes[2] == 1 {
+            modes[2] = 0;
+        }
+        let encoded = modes[..width - 1]
+            .iter()
+            .rev()
+            .fold(0, |digits, mode| digits * 10 + mode)
+            * 100
+            + opcode;
+        std::iter::once(encoded)
+            .chain(parameters.into_iter().take(width - 1))
+            .collect()
+    })
+}
+
+proptest! {
+    #[test]
+    fn random_programs_behave_the_same(
+        instructions in proptest::collection::vec(instruction(), 1..12),
+    ) {
+        let program = instructions.concat();
+        let limits = intcode::Limits {
+            step_limit: Some(500),
+            ..intcode::Limits::default()
+        };
+
+        let mut interpreted = intcode::Machine::new(program.clone()).with_limits(limits);
+        let mut compiled =
+            jit::JitMachine::from(intcode::Machine::new(program).with_limits(limits));
+
+        prop_assert_eq!(compiled.run_to_completion(), interpreted.run_to_completion());
+        let compiled = compiled.into_machine();
+        prop_assert_eq!(compiled.ip(), interpreted.ip());
+        prop_assert_eq!(compiled.relative_base(), interpreted.relative_base());
+        prop_assert_eq!(compiled.steps(), interpreted.steps());
+        prop_assert_eq!(compiled.into_memory(), interpreted.into_memory());
+    }
+}